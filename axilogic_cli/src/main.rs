@@ -0,0 +1,650 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    ffi::OsString,
+    fs,
+    io::{self, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    process::{self, ExitCode},
+    sync::{mpsc, Condvar, Mutex, OnceLock},
+    thread,
+    time::Duration,
+};
+
+use axilogic_core::{
+    asm,
+    kernel::{Classical, Empty, Intuitionistic},
+    loader::Loader,
+    vm::{Limits, Verifier},
+};
+
+const USAGE: &str = "Usage: axilogic [OPTIONS] <FILE>...
+
+Verify each proof file with a fresh verifier and print a summary.
+The modules the files require are verified once each, after the modules they
+require, and shared by the files; files and modules that do not depend on
+each other are verified in parallel. Files are reported in the order given.
+A failing def/hyp block is discarded and verification continues, so every
+independent error in a file is reported.
+With --check-hyps, propositional hypotheses are checked by truth table, with
+a warning for each one that is not classically valid, for a jointly
+unsatisfiable set and for those left unchecked once the limits are reached.
+Exits with a non-zero status if any file fails.
+
+Options:
+    --kernel <KERNEL>  classical (default), intuitionistic or empty
+    -L, --lib <DIR>    library root for `req a::b::x` (default: the directory
+                       of each file); may be given several times
+    --max-steps <N>    reduction steps allowed to a single instruction
+    --max-term-size <N>
+                       nodes allowed in a term once flushed
+    --max-stack <N>    entries allowed on the stack
+    --timeout <SECS>   time allowed to each file, and separately to each
+                       module it requires
+    -j, --jobs <N>     files verified at once (default: the number of CPUs)
+    --check-hyps       check propositional hypotheses by truth table";
+
+/// Stack of each worker thread, as large as a typical main thread's
+const WORKER_STACK: usize = 8 << 20;
+
+#[derive(Clone, Copy)]
+enum KernelName {
+    Classical,
+    Intuitionistic,
+    Empty,
+}
+
+impl KernelName {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "classical" => Some(Self::Classical),
+            "intuitionistic" => Some(Self::Intuitionistic),
+            "empty" => Some(Self::Empty),
+            _ => None,
+        }
+    }
+}
+
+/// Errors and warnings about a file
+struct Report {
+    /// Empty if the file passes
+    diags: Vec<String>,
+    warnings: Vec<String>,
+}
+
+impl From<Vec<String>> for Report {
+    fn from(diags: Vec<String>) -> Self {
+        Self {
+            diags,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+/// How every file and module is verified
+struct Options {
+    kernel: KernelName,
+    limits: Limits,
+    check_hyps: bool,
+}
+
+impl Options {
+    fn verifier(&self) -> Result<Verifier, String> {
+        let vm = match self.kernel {
+            KernelName::Classical => Verifier::with_kernel(Default::default(), &Classical),
+            KernelName::Intuitionistic => {
+                Verifier::with_kernel(Default::default(), &Intuitionistic)
+            }
+            KernelName::Empty => Verifier::with_kernel(Default::default(), &Empty),
+        };
+        let mut vm = vm.map_err(|e| e.to_string())?;
+        vm.set_limits(self.limits.clone());
+        Ok(vm)
+    }
+}
+
+/// The library roots of a file: those given, or else its directory
+fn roots_of(path: &Path, roots: &[PathBuf]) -> Vec<PathBuf> {
+    if roots.is_empty() {
+        let dir = path.parent().map_or_else(PathBuf::new, PathBuf::from);
+        vec![dir]
+    } else {
+        roots.to_vec()
+    }
+}
+
+/// A module verified on its own, shared read-only by whatever requires it
+struct Built {
+    image: Vec<u8>,
+    /// The module and every module it loaded
+    closure: Vec<String>,
+}
+
+/// Add the symbols of verified modules, so that `loader` does not verify
+/// them again. A module whose image fails to load is left to `loader`.
+fn preload(vm: &mut Verifier, loader: &mut Loader, built: &[&Built]) -> Vec<String> {
+    let mut closure = Vec::new();
+    for x in built {
+        if vm.load(&x.image[..]).is_ok() {
+            for name in &x.closure {
+                loader.mark_loaded(name);
+                if !closure.contains(name) {
+                    closure.push(name.clone());
+                }
+            }
+        }
+    }
+    closure
+}
+
+/// Verify `module` on top of the modules it requires, or `None` if it fails;
+/// its errors are then reported by the files that require it
+fn build(name: &str, roots: &[PathBuf], deps: &[&Built], opts: &Options) -> Option<Built> {
+    let mut vm = opts.verifier().ok()?;
+    let mut loader = Loader::new(roots.to_vec());
+    let mut closure = preload(&mut vm, &mut loader, deps);
+    loader.load(&mut vm, name).ok()?;
+    let mut image = Vec::new();
+    vm.save(&mut image).ok()?;
+    closure.push(name.into());
+    Some(Built { image, closure })
+}
+
+fn verify(path: &Path, roots: &[PathBuf], deps: &[&Built], opts: &Options) -> Report {
+    let src = match fs::read_to_string(path) {
+        Ok(src) => src,
+        Err(e) => return vec![e.to_string()].into(),
+    };
+    let mut vm = match opts.verifier() {
+        Ok(vm) => vm,
+        Err(e) => return vec![e].into(),
+    };
+    let prog = match asm::parse(&src) {
+        Ok(prog) => prog,
+        Err(e) => return vec![e.to_string()].into(),
+    };
+    let mut loader = Loader::new(roots_of(path, roots));
+    preload(&mut vm, &mut loader, deps);
+    let prog = match loader.link(&mut vm, prog) {
+        Ok(prog) => prog,
+        Err(e) => return vec![e.to_string()].into(),
+    };
+    let mut diags: Vec<_> = asm::execute_recovering(&mut vm, &prog)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    if let Err(e) = vm.finish() {
+        diags.extend(e.leftovers.iter().map(|x| format!("end of file: {}", x)));
+    }
+    if !opts.check_hyps {
+        return diags.into();
+    }
+    let model = vm.check_hyps();
+    let mut warnings: Vec<_> = model.invalid.iter().map(ToString::to_string).collect();
+    if !model.unsatisfiable.is_empty() {
+        warnings.push(format!(
+            "hyps {} are jointly unsatisfiable",
+            model.unsatisfiable.join(", ")
+        ));
+    }
+    if !model.skipped.is_empty() {
+        warnings.push(format!(
+            "hyps {} were left out of the truth-table check",
+            model.skipped.join(", ")
+        ));
+    }
+    Report { diags, warnings }
+}
+
+/// The modules required by a set of files, as a DAG over the modules and
+/// then the files
+struct Plan {
+    /// Each module by its library roots and name
+    modules: Vec<(Vec<PathBuf>, String)>,
+    /// For each module and then each file, the modules to build first
+    deps: Vec<Vec<usize>>,
+    /// Whether each module is off any import cycle, and so can be built
+    acyclic: Vec<bool>,
+}
+
+impl Plan {
+    /// Read the files and the modules they require, transitively. Modules
+    /// on an import cycle or requiring one are left out, for the files to
+    /// load and report themselves.
+    fn new(files: &[PathBuf], roots: &[PathBuf]) -> Self {
+        let mut plan = Plan {
+            modules: Vec::new(),
+            deps: Vec::new(),
+            acyclic: Vec::new(),
+        };
+        let mut index = HashMap::new();
+        let mut intern = |plan: &mut Plan, key: (Vec<PathBuf>, String)| {
+            *index.entry(key.clone()).or_insert_with(|| {
+                plan.modules.push(key);
+                plan.deps.push(Vec::new());
+                plan.modules.len() - 1
+            })
+        };
+        let mut file_deps = Vec::new();
+        for path in files {
+            let roots = roots_of(path, roots);
+            let loader = Loader::new(roots.clone());
+            let prog = fs::read_to_string(path)
+                .ok()
+                .and_then(|src| asm::parse(&src).ok());
+            let required = prog.map_or_else(Vec::new, |prog| loader.requires(None, &prog));
+            let deps: Vec<_> = required
+                .into_iter()
+                .map(|m| intern(&mut plan, (roots.clone(), m)))
+                .collect();
+            file_deps.push(deps);
+        }
+        // modules are appended as they are found
+        let mut i = 0;
+        while i < plan.modules.len() {
+            let (roots, name) = plan.modules[i].clone();
+            let loader = Loader::new(roots.clone());
+            let required = loader.dependencies(&name).unwrap_or_default();
+            plan.deps[i] = required
+                .into_iter()
+                .map(|m| intern(&mut plan, (roots.clone(), m)))
+                .collect();
+            i += 1;
+        }
+        plan.deps.extend(file_deps);
+        plan.drop_cycles();
+        plan
+    }
+
+    /// Remove the modules that never become ready from the dependencies
+    fn drop_cycles(&mut self) {
+        let n = self.modules.len();
+        let mut waiting: Vec<_> = self.deps[..n].iter().map(Vec::len).collect();
+        let mut dependents = vec![Vec::new(); n];
+        for (i, deps) in self.deps[..n].iter().enumerate() {
+            for &d in deps {
+                dependents[d].push(i);
+            }
+        }
+        let mut ready: Vec<_> = (0..n).filter(|&i| waiting[i] == 0).collect();
+        let mut acyclic = vec![false; n];
+        while let Some(i) = ready.pop() {
+            acyclic[i] = true;
+            for &j in &dependents[i] {
+                waiting[j] -= 1;
+                if waiting[j] == 0 {
+                    ready.push(j);
+                }
+            }
+        }
+        for (i, deps) in self.deps.iter_mut().enumerate() {
+            if i < n && !acyclic[i] {
+                deps.clear();
+            } else {
+                deps.retain(|&d| acyclic[d]);
+            }
+        }
+        self.acyclic = acyclic;
+    }
+}
+
+/// Jobs of [`verify_all`] that are ready, and what the others wait for
+struct Queue {
+    ready: VecDeque<usize>,
+    /// Number of unfinished dependencies of each job
+    waiting: Vec<usize>,
+    /// Jobs not finished yet
+    left: usize,
+}
+
+/// Verify `files` on `jobs` threads, each file with its own verifier, and
+/// pass the reports to `out` in the order of `files`.
+///
+/// The modules the files require are verified first, each once and as soon
+/// as the modules it requires are, and their symbols are shared as images.
+fn verify_all(
+    files: &[PathBuf],
+    jobs: NonZeroUsize,
+    roots: &[PathBuf],
+    opts: &Options,
+    mut out: impl FnMut(&PathBuf, Report),
+) {
+    let plan = Plan::new(files, roots);
+    // job `i` builds module `i`, or verifies file `i - n_modules`
+    let n_modules = plan.modules.len();
+    let built: Vec<OnceLock<Option<Built>>> = (0..n_modules).map(|_| OnceLock::new()).collect();
+    let mut dependents = vec![Vec::new(); plan.deps.len()];
+    for (i, deps) in plan.deps.iter().enumerate() {
+        for &d in deps {
+            dependents[d].push(i);
+        }
+    }
+    let waiting: Vec<_> = plan.deps.iter().map(Vec::len).collect();
+    let scheduled: Vec<_> = (0..plan.deps.len())
+        .filter(|&i| i >= n_modules || plan.acyclic[i])
+        .collect();
+    let queue = Mutex::new(Queue {
+        ready: scheduled
+            .iter()
+            .copied()
+            .filter(|&i| waiting[i] == 0)
+            .collect(),
+        waiting,
+        left: scheduled.len(),
+    });
+    let wake = Condvar::new();
+
+    let (tx, rx) = mpsc::channel();
+    thread::scope(|s| {
+        for _ in 0..jobs.get().min(scheduled.len()) {
+            let (tx, plan, built) = (tx.clone(), &plan, &built);
+            let (queue, wake, dependents) = (&queue, &wake, &dependents);
+            let work = move || loop {
+                let mut q = queue.lock().unwrap();
+                let i = loop {
+                    if let Some(i) = q.ready.pop_front() {
+                        break i;
+                    }
+                    if q.left == 0 {
+                        return;
+                    }
+                    q = wake.wait(q).unwrap();
+                };
+                drop(q);
+                let deps: Vec<&Built> = plan.deps[i]
+                    .iter()
+                    .filter_map(|&d| built[d].get()?.as_ref())
+                    .collect();
+                if i < n_modules {
+                    let (roots, name) = &plan.modules[i];
+                    let _ = built[i].set(build(name, roots, &deps, opts));
+                } else {
+                    let path = &files[i - n_modules];
+                    // the receiver outlives the workers
+                    let _ = tx.send((i - n_modules, verify(path, roots, &deps, opts)));
+                }
+                let mut q = queue.lock().unwrap();
+                q.left -= 1;
+                for &j in &dependents[i] {
+                    q.waiting[j] -= 1;
+                    if q.waiting[j] == 0 {
+                        q.ready.push_back(j);
+                    }
+                }
+                wake.notify_all();
+            };
+            thread::Builder::new()
+                .stack_size(WORKER_STACK)
+                .spawn_scoped(s, work)
+                .expect("failed to spawn a worker thread");
+        }
+        drop(tx);
+        // a report waits for those of the files before it
+        let mut pending = BTreeMap::new();
+        let mut done = 0;
+        for (i, report) in rx {
+            pending.insert(i, report);
+            while let Some(report) = pending.remove(&done) {
+                out(&files[done], report);
+                done += 1;
+            }
+        }
+    });
+}
+
+/// The operand of a numeric option
+fn parse_operand<T: std::str::FromStr>(operand: Option<OsString>) -> Option<T> {
+    operand?.to_str()?.parse().ok()
+}
+
+/// Stop once stdout cannot be written, quietly if its reader is gone, as
+/// when the output is piped into `head`
+fn written(res: io::Result<()>) {
+    if let Err(e) = res {
+        if e.kind() != io::ErrorKind::BrokenPipe {
+            eprintln!("Cannot write to stdout: {}", e);
+        }
+        process::exit(1);
+    }
+}
+
+fn main() -> ExitCode {
+    let mut stdout = io::stdout().lock();
+    let mut files = Vec::new();
+    let mut kernel = KernelName::Classical;
+    let mut roots = Vec::new();
+    let mut limits = Limits::default();
+    let mut jobs = thread::available_parallelism().unwrap_or(NonZeroUsize::MIN);
+    let mut check_hyps = false;
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("-h" | "--help") => {
+                written(writeln!(stdout, "{}", USAGE));
+                return ExitCode::SUCCESS;
+            }
+            Some("--kernel") => {
+                let name = args.next();
+                match name
+                    .as_ref()
+                    .and_then(|x| x.to_str())
+                    .and_then(KernelName::parse)
+                {
+                    Some(k) => kernel = k,
+                    None => {
+                        eprintln!("Expected a kernel name after --kernel\n\n{}", USAGE);
+                        return ExitCode::from(2);
+                    }
+                }
+            }
+            Some("-L" | "--lib") => match args.next() {
+                Some(dir) => roots.push(PathBuf::from(dir)),
+                None => {
+                    eprintln!(
+                        "Expected a directory after {}\n\n{}",
+                        arg.to_string_lossy(),
+                        USAGE
+                    );
+                    return ExitCode::from(2);
+                }
+            },
+            Some(opt @ ("--max-steps" | "--max-term-size" | "--max-stack")) => {
+                let Some(n) = parse_operand(args.next()) else {
+                    eprintln!("Expected a number after {}\n\n{}", opt, USAGE);
+                    return ExitCode::from(2);
+                };
+                match opt {
+                    "--max-steps" => limits.max_steps = Some(n),
+                    "--max-term-size" => limits.max_term_size = Some(n),
+                    _ => limits.max_stack = Some(n),
+                }
+            }
+            Some("--timeout") => {
+                let secs = parse_operand(args.next());
+                match secs.and_then(|x| Duration::try_from_secs_f64(x).ok()) {
+                    Some(t) => limits.time = Some(t),
+                    None => {
+                        eprintln!("Expected a number of seconds after --timeout\n\n{}", USAGE);
+                        return ExitCode::from(2);
+                    }
+                }
+            }
+            Some("-j" | "--jobs") => match parse_operand(args.next()) {
+                Some(n) => jobs = n,
+                None => {
+                    eprintln!(
+                        "Expected a positive number after {}\n\n{}",
+                        arg.to_string_lossy(),
+                        USAGE
+                    );
+                    return ExitCode::from(2);
+                }
+            },
+            Some("--check-hyps") => check_hyps = true,
+            Some(s) if s.starts_with('-') => {
+                eprintln!("Unknown option: {}\n\n{}", s, USAGE);
+                return ExitCode::from(2);
+            }
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+    if files.is_empty() {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    }
+    let mut failed = 0;
+    let opts = Options {
+        kernel,
+        limits,
+        check_hyps,
+    };
+    verify_all(&files, jobs, &roots, &opts, |path, report| {
+        if report.diags.is_empty() {
+            written(writeln!(stdout, "PASS {}", path.display()));
+        } else {
+            failed += 1;
+            written(writeln!(stdout, "FAIL {}", path.display()));
+            for d in &report.diags {
+                written(writeln!(stdout, "    {}", d));
+            }
+        }
+        for w in &report.warnings {
+            written(writeln!(stdout, "    warning: {}", w));
+        }
+    });
+    written(writeln!(
+        stdout,
+        "{} passed, {} failed, {} total",
+        files.len() - failed,
+        failed,
+        files.len()
+    ));
+    if failed > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `p0 -> p1 -> ... -> p(n - 1) -> p0`, a term in synthetic mode
+    fn chain(n: usize) -> String {
+        let mut term = "req p0".to_string();
+        for i in (0..n).rev() {
+            term = format!("req sys::imply syn req p{} app syn {} app", i, term);
+        }
+        term
+    }
+
+    #[test]
+    fn test_check_hyps_timeout() {
+        let dir = std::env::temp_dir().join(format!("axilogic-cli-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // each hypothesis takes a million valuations to check
+        let atoms = 20;
+        let mut src: String = (0..atoms).map(|i| format!("obj 0 p{}\n", i)).collect();
+        for i in 0..50 {
+            src += &format!("syn {} hyp h{}\n", chain(atoms), i);
+        }
+        let file = dir.join("many.thm");
+        fs::write(&file, src).unwrap();
+
+        let mut opts = Options {
+            kernel: KernelName::Classical,
+            limits: Limits {
+                time: Some(Duration::from_millis(200)),
+                ..Limits::default()
+            },
+            check_hyps: true,
+        };
+        let report = verify(&file, &[], &[], &opts);
+        assert!(report.diags.is_empty(), "{:?}", report.diags);
+        let [warning] = &report.warnings[..] else {
+            panic!("{:?}", report.warnings)
+        };
+        assert!(
+            warning.ends_with("were left out of the truth-table check"),
+            "{}",
+            warning
+        );
+        // not checked at all without the flag
+        opts.check_hyps = false;
+        let report = verify(&file, &[], &[], &opts);
+        assert!(report.diags.is_empty() && report.warnings.is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_verify_all() {
+        let dir = std::env::temp_dir().join(format!("axilogic-all-{}", std::process::id()));
+        let sources = [
+            (
+                "lib/base.thm",
+                "syn uni var qed req sys::imply syn arg 1 app syn arg 1 app qed hyp self_imply",
+            ),
+            ("lib/more.thm", "syn req super::base::self_imply hyp again"),
+            ("lib/bad.thm", "syn req nope hyp x"),
+            ("cyc/a.thm", "syn req cyc::b::y hyp x"),
+            ("cyc/b.thm", "syn req cyc::a::x hyp y"),
+            ("f1.thm", "syn req lib::more::again hyp t"),
+            (
+                "f2.thm",
+                "syn req lib::base::self_imply hyp t syn req prelude::dne hyp u",
+            ),
+            ("f3.thm", "syn req lib::bad::x hyp t"),
+            ("f4.thm", "syn req cyc::a::x hyp t"),
+            ("f5.thm", "syn req lib::more::again req nope::x hyp t"),
+        ];
+        for (path, src) in sources {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, src).unwrap();
+        }
+        let files: Vec<_> = (1..=5).map(|i| dir.join(format!("f{}.thm", i))).collect();
+
+        // each module once, and the cycle left to the file
+        let plan = Plan::new(&files, &[]);
+        let mut modules: Vec<_> = plan.modules.iter().map(|(_, x)| x.as_str()).collect();
+        modules.sort();
+        assert_eq!(
+            modules,
+            [
+                "cyc::a",
+                "cyc::b",
+                "lib::bad",
+                "lib::base",
+                "lib::more",
+                "prelude"
+            ]
+        );
+        assert_eq!(plan.acyclic.iter().filter(|x| **x).count(), 4);
+
+        // the same reports as loading the modules in each file
+        let opts = Options {
+            kernel: KernelName::Classical,
+            limits: Limits::default(),
+            check_hyps: true,
+        };
+        let expected: Vec<_> = files
+            .iter()
+            .map(|x| {
+                let report = verify(x, &[], &[], &opts);
+                (report.diags, report.warnings)
+            })
+            .collect();
+        assert!(expected[0].0.is_empty() && expected[1].0.is_empty());
+        assert!(expected[2..].iter().all(|x| x.0.len() == 1));
+        for jobs in [1, 4] {
+            let mut reports = Vec::new();
+            let jobs = NonZeroUsize::new(jobs).unwrap();
+            verify_all(&files, jobs, &[], &opts, |_, report| {
+                reports.push((report.diags, report.warnings))
+            });
+            assert_eq!(reports, expected);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
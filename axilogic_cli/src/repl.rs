@@ -0,0 +1,247 @@
+use std::{
+    fs,
+    io::{self, BufRead, Write},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use axilogic_core::{
+    asm::{self, Instruction, Pos},
+    loader::Loader,
+    vm::{Checkpoint, Verifier},
+};
+
+const USAGE: &str = "Usage: axilogic-repl [-L <DIR>]...
+
+Run instructions one line at a time and show the stack after each step.
+
+Options:
+    -L, --lib <DIR>  library root for `req a::b::x` (default: the current
+                     directory); may be given several times";
+
+const HELP: &str = "Enter instructions, or one of
+    :undo              revert the last successful line or :load
+    :show <symbol>     print a symbol
+    :symbols [prefix]  list the symbols starting with prefix
+    :load <file>       run a proof file in this session
+    :help              print this message
+    :quit              leave (also end of input)";
+
+/// A line that changes the state
+enum Step {
+    Run(Vec<(Pos, Instruction)>),
+    Load(PathBuf),
+}
+
+struct Session {
+    vm: Verifier,
+    loader: Loader,
+    /// The state before each successful step
+    history: Vec<(Checkpoint, Loader)>,
+}
+
+impl Session {
+    fn new(roots: Vec<PathBuf>) -> Self {
+        Self {
+            loader: Loader::new(roots),
+            vm: Verifier::default(),
+            history: Vec::new(),
+        }
+    }
+
+    fn apply(&mut self, step: Step) -> Result<(), String> {
+        let prog = match step {
+            Step::Run(prog) => prog,
+            Step::Load(path) => {
+                let src = fs::read_to_string(path).map_err(|e| e.to_string())?;
+                asm::parse(&src).map_err(|e| e.to_string())?
+            }
+        };
+        let prog = self
+            .loader
+            .link(&mut self.vm, prog)
+            .map_err(|e| e.to_string())?;
+        asm::execute(&mut self.vm, &prog).map_err(|e| e.to_string())
+    }
+
+    /// Run a step, leaving the state as it was if any part of it fails
+    fn step(&mut self, step: Step) -> Result<(), String> {
+        let before = (self.vm.checkpoint(), self.loader.clone());
+        match self.apply(step) {
+            Ok(()) => {
+                self.history.push(before);
+                Ok(())
+            }
+            Err(e) => {
+                self.vm.restore(&before.0).map_err(|e| e.to_string())?;
+                self.loader = before.1;
+                Err(e)
+            }
+        }
+    }
+
+    fn print_stack(&mut self) {
+        let stack = self.vm.show_stack();
+        println!(
+            "  [stack depth {}, syn depth {}]",
+            stack.len(),
+            self.vm.syn_depth()
+        );
+        for (i, line) in stack.iter().enumerate() {
+            println!("  {:>3} | {}", i + 1, line);
+        }
+    }
+
+    /// Returns `false` to quit
+    fn command(&mut self, line: &str) -> bool {
+        let (cmd, arg) = match line.split_once(char::is_whitespace) {
+            Some((cmd, arg)) => (cmd, arg.trim()),
+            None => (line, ""),
+        };
+        match cmd {
+            ":quit" | ":q" => return false,
+            ":help" => println!("{}", HELP),
+            ":undo" => match self.history.pop() {
+                Some((cp, loader)) => match self.vm.restore(&cp) {
+                    Ok(()) => {
+                        self.loader = loader;
+                        self.print_stack();
+                    }
+                    Err(e) => println!("{}", e),
+                },
+                None => println!("Nothing to undo"),
+            },
+            ":show" if !arg.is_empty() => match self.vm.show(arg) {
+                Ok(s) => println!("{}: {}", arg, s),
+                Err(e) => println!("{}", e),
+            },
+            ":symbols" => {
+                let mut names: Vec<_> = self.vm.symbols().filter(|x| x.starts_with(arg)).collect();
+                names.sort();
+                for name in names {
+                    println!("{}", name);
+                }
+            }
+            ":load" if !arg.is_empty() => {
+                if let Err(e) = self.step(Step::Load(arg.into())) {
+                    println!("error: {}", e);
+                }
+                self.print_stack();
+            }
+            _ => println!("Unknown command: {}\n{}", line, HELP),
+        }
+        true
+    }
+
+    fn line(&mut self, line: &str) {
+        match asm::parse(line) {
+            Ok(prog) if prog.is_empty() => (),
+            Ok(prog) => {
+                if let Err(e) = self.step(Step::Run(prog)) {
+                    println!("error: {}", e);
+                }
+                self.print_stack();
+            }
+            Err(e) => println!("error: {}", e),
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let mut roots = Vec::new();
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("-h" | "--help") => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            Some("-L" | "--lib") => match args.next() {
+                Some(dir) => roots.push(PathBuf::from(dir)),
+                None => {
+                    eprintln!(
+                        "Expected a directory after {}\n\n{}",
+                        arg.to_string_lossy(),
+                        USAGE
+                    );
+                    return ExitCode::from(2);
+                }
+            },
+            _ => {
+                eprintln!("Unknown argument: {}\n\n{}", arg.to_string_lossy(), USAGE);
+                return ExitCode::from(2);
+            }
+        }
+    }
+    if roots.is_empty() {
+        roots.push(PathBuf::from("."));
+    }
+    let mut session = Session::new(roots);
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("> ");
+        io::stdout().flush().unwrap();
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            Some(Err(e)) => {
+                eprintln!("{}", e);
+                return ExitCode::FAILURE;
+            }
+            None => break,
+        };
+        let line = line.trim();
+        if line.starts_with(':') {
+            if !session.command(line) {
+                break;
+            }
+        } else {
+            session.line(line);
+        }
+    }
+    println!();
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axilogic_core::isa::InstructionSet;
+
+    #[test]
+    fn test_session() {
+        let dir = std::env::temp_dir().join(format!("axilogic-repl-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut session = Session::new(vec![dir.clone()]);
+
+        session.line("obj 0 p");
+        session.line("syn req sys::imply syn req p app syn req p app hyp p_p");
+        assert!(session.vm.has("p_p"));
+        assert!(session.command(":undo"));
+        assert!(!session.vm.has("p_p") && session.vm.has("p"));
+
+        // a failed line leaves the state as it was, and the session goes on
+        session.line("syn req p");
+        session.line("req nope hyp bad");
+        assert_eq!(session.vm.show_stack().len(), 2);
+        assert!(!session.vm.has("bad"));
+        session.line("hyp p2");
+        assert!(session.vm.has("p2") && session.vm.show_stack().is_empty());
+
+        let file = dir.join("proof.thm");
+        fs::write(&file, "req sys::l1 syn req p app syn req p2 app def t").unwrap();
+        session.command(&format!(":load {}", file.display()));
+        assert!(session.vm.has("t"));
+        // undone as a whole
+        session.command(":undo");
+        assert!(!session.vm.has("t") && session.vm.has("p2"));
+        // a file that fails adds nothing
+        fs::write(&file, "req sys::l1 def t req nope def u").unwrap();
+        session.command(&format!(":load {}", file.display()));
+        assert!(!session.vm.has("t"));
+        assert!(session.vm.show_stack().is_empty());
+
+        assert!(!session.command(":quit"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
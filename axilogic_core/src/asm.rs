@@ -0,0 +1,559 @@
+//! Textual assembly for [`InstructionSet`].
+//!
+//! A program is a stream of whitespace separated tokens. Each instruction is
+//! a mnemonic followed by its operands, so several instructions may share a
+//! line and an instruction may span lines. Everything after `#` up to the end
+//! of the line is a comment.
+//!
+//! | Mnemonic    | Operands         | Method                      |
+//! |-------------|------------------|-----------------------------|
+//! | `uni`       |                  | [`InstructionSet::uni`]     |
+//! | `var`       |                  | [`InstructionSet::var`]     |
+//! | `hkt`       |                  | [`InstructionSet::hkt`]     |
+//! | `qed`       |                  | [`InstructionSet::qed`]     |
+//! | `mp`        |                  | [`InstructionSet::mp`]      |
+//! | `app`       |                  | [`InstructionSet::app`]     |
+//! | `req <sym>` | symbol           | [`InstructionSet::req`]     |
+//! | `def <sym>` | symbol           | [`InstructionSet::def`]     |
+//! | `hyp <sym>` | symbol           | [`InstructionSet::hyp`]     |
+//! | `obj <n> <sym>` | arity, symbol | [`InstructionSet::obj`]    |
+//! | `syn`       |                  | [`InstructionSet::syn`]     |
+//! | `sat`       |                  | [`InstructionSet::sat`]     |
+//! | `arg <n>`   | index, from 1    | [`InstructionSet::arg`]     |
+//! | `dup`       |                  | [`InstructionSet::dup`]     |
+//! | `pick <n>`  | depth, from 1    | [`InstructionSet::pick`]    |
+//! | `swap`      |                  | [`InstructionSet::swap`]    |
+//! | `drop`      |                  | [`InstructionSet::drop`]    |
+//!
+//! ```text
+//! # a -> a, for any a
+//! syn
+//!   uni var qed
+//!     req sys::imply
+//!     syn arg 1 app
+//!     syn arg 1 app
+//!   qed
+//! hyp refl
+//! ```
+
+use std::{collections::HashSet, error::Error as StdError, fmt, num::NonZeroUsize};
+
+use crate::{
+    err::{OperationError, Result},
+    isa::{InstructionSet, Recover},
+    vm::Limit,
+};
+
+/// Position of a token in the source, both counted from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pos {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instruction {
+    Uni,
+    Var,
+    Hkt,
+    Qed,
+    Mp,
+    App,
+    Req(String),
+    Def(String),
+    Hyp(String),
+    Obj(usize, String),
+    Syn,
+    Sat,
+    Arg(NonZeroUsize),
+    Dup,
+    Pick(NonZeroUsize),
+    Swap,
+    Drop,
+}
+
+impl Instruction {
+    pub fn mnemonic(&self) -> &'static str {
+        use Instruction::*;
+        match self {
+            Uni => "uni",
+            Var => "var",
+            Hkt => "hkt",
+            Qed => "qed",
+            Mp => "mp",
+            App => "app",
+            Req(_) => "req",
+            Def(_) => "def",
+            Hyp(_) => "hyp",
+            Obj(..) => "obj",
+            Syn => "syn",
+            Sat => "sat",
+            Arg(_) => "arg",
+            Dup => "dup",
+            Pick(_) => "pick",
+            Swap => "swap",
+            Drop => "drop",
+        }
+    }
+
+    pub fn apply<T: InstructionSet + ?Sized>(&self, vm: &mut T) -> Result<()> {
+        use Instruction::*;
+        match self {
+            Uni => vm.uni(),
+            Var => vm.var(),
+            Hkt => vm.hkt(),
+            Qed => vm.qed(),
+            Mp => vm.mp(),
+            App => vm.app(),
+            Req(s) => vm.req(s),
+            Def(s) => vm.def(s.clone()),
+            Hyp(s) => vm.hyp(s.clone()),
+            Obj(n, s) => vm.obj(*n, s.clone()),
+            Syn => vm.syn(),
+            Sat => vm.sat(),
+            Arg(n) => vm.arg(*n),
+            Dup => vm.dup(),
+            Pick(n) => vm.pick(*n),
+            Swap => vm.swap(),
+            Drop => vm.drop(),
+        }
+    }
+
+    /// The symbol introduced by `def`, `hyp` or `obj`, which ends a block
+    pub fn export_name(&self) -> Option<&str> {
+        use Instruction::*;
+        match self {
+            Def(s) | Hyp(s) | Obj(_, s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Instruction::*;
+        write!(f, "{}", self.mnemonic())?;
+        match self {
+            Req(s) | Def(s) | Hyp(s) => write!(f, " {}", s),
+            Obj(n, s) => write!(f, " {} {}", n, s),
+            Arg(n) | Pick(n) => write!(f, " {}", n),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    UnknownMnemonic(String),
+    MissingOperand(&'static str),
+    InvalidOperand(&'static str, String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub pos: Pos,
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ParseErrorKind::*;
+        write!(f, "{}: ", self.pos)?;
+        match &self.kind {
+            UnknownMnemonic(s) => write!(f, "unknown mnemonic `{}`", s),
+            MissingOperand(what) => write!(f, "expected {}", what),
+            InvalidOperand(what, s) => write!(f, "expected {}, found `{}`", what, s),
+        }
+    }
+}
+
+impl StdError for ParseError {}
+
+/// Failure of [`run`]: either the source does not parse, or an instruction
+/// was rejected by the instruction set.
+#[derive(Debug)]
+pub enum Error {
+    Parse(ParseError),
+    Operation {
+        pos: Pos,
+        instruction: Instruction,
+        err: OperationError,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Parse(e) => write!(f, "{}", e),
+            Error::Operation {
+                pos,
+                instruction,
+                err,
+            } => write!(f, "{}: `{}`: {}", pos, instruction, err),
+        }
+    }
+}
+
+impl StdError for Error {}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::Parse(e)
+    }
+}
+
+struct Tokens<'a> {
+    lines: std::iter::Enumerate<std::str::Lines<'a>>,
+    line: Vec<(Pos, &'a str)>,
+    last: Pos,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(src: &'a str) -> Self {
+        Self {
+            lines: src.lines().enumerate(),
+            line: Vec::new(),
+            last: Pos { line: 1, col: 1 },
+        }
+    }
+
+    fn split_line(line_no: usize, s: &'a str) -> Vec<(Pos, &'a str)> {
+        let s = s.split('#').next().unwrap();
+        let mut tokens = Vec::new();
+        let mut start = None;
+        for (col, (i, c)) in s.char_indices().chain([(s.len(), ' ')]).enumerate() {
+            match (c.is_whitespace(), start) {
+                (false, None) => start = Some((col, i)),
+                (true, Some((col, j))) => {
+                    let pos = Pos {
+                        line: line_no + 1,
+                        col: col + 1,
+                    };
+                    tokens.push((pos, &s[j..i]));
+                    start = None;
+                }
+                _ => (),
+            }
+        }
+        tokens.reverse();
+        tokens
+    }
+
+    fn operand(&mut self, what: &'static str) -> std::result::Result<(Pos, &'a str), ParseError> {
+        self.next().ok_or(ParseError {
+            pos: self.last,
+            kind: ParseErrorKind::MissingOperand(what),
+        })
+    }
+
+    fn number<T: std::str::FromStr>(
+        &mut self,
+        what: &'static str,
+    ) -> std::result::Result<T, ParseError> {
+        let (pos, s) = self.operand(what)?;
+        s.parse().map_err(|_| ParseError {
+            pos,
+            kind: ParseErrorKind::InvalidOperand(what, s.into()),
+        })
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = (Pos, &'a str);
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(token) = self.line.pop() {
+                self.last = token.0;
+                return Some(token);
+            }
+            let (line_no, s) = self.lines.next()?;
+            self.line = Self::split_line(line_no, s);
+        }
+    }
+}
+
+/// Parse a whole program, stopping at the first malformed instruction.
+pub fn parse(src: &str) -> std::result::Result<Vec<(Pos, Instruction)>, ParseError> {
+    use Instruction::*;
+    let mut tokens = Tokens::new(src);
+    let mut prog = Vec::new();
+    while let Some((pos, s)) = tokens.next() {
+        let ins = match s {
+            "uni" => Uni,
+            "var" => Var,
+            "hkt" => Hkt,
+            "qed" => Qed,
+            "mp" => Mp,
+            "app" => App,
+            "req" => Req(tokens.operand("symbol")?.1.into()),
+            "def" => Def(tokens.operand("symbol")?.1.into()),
+            "hyp" => Hyp(tokens.operand("symbol")?.1.into()),
+            "obj" => {
+                let n = tokens.number("arity")?;
+                Obj(n, tokens.operand("symbol")?.1.into())
+            }
+            "syn" => Syn,
+            "sat" => Sat,
+            "arg" => Arg(tokens.number("argument index")?),
+            "dup" => Dup,
+            "pick" => Pick(tokens.number("stack depth")?),
+            "swap" => Swap,
+            "drop" => Drop,
+            s => {
+                return Err(ParseError {
+                    pos,
+                    kind: ParseErrorKind::UnknownMnemonic(s.into()),
+                })
+            }
+        };
+        prog.push((pos, ins));
+    }
+    Ok(prog)
+}
+
+/// Execute parsed instructions, stopping at the first failure.
+pub fn execute<T: InstructionSet + ?Sized>(
+    vm: &mut T,
+    prog: &[(Pos, Instruction)],
+) -> std::result::Result<(), Error> {
+    for (pos, ins) in prog {
+        ins.apply(vm).map_err(|err| Error::Operation {
+            pos: *pos,
+            instruction: ins.clone(),
+            err,
+        })?;
+    }
+    Ok(())
+}
+
+/// Parse and execute a program.
+pub fn run<T: InstructionSet + ?Sized>(vm: &mut T, src: &str) -> std::result::Result<(), Error> {
+    execute(vm, &parse(src)?)
+}
+
+#[derive(Debug)]
+pub enum DiagnosticKind {
+    Error(OperationError),
+    /// Requiring a symbol whose own block failed
+    DependsOnFailed(String),
+}
+
+/// A failed block reported by [`execute_recovering`]
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub pos: Pos,
+    pub instruction: Instruction,
+    /// The symbol the discarded block would have exported
+    pub symbol: Option<String>,
+    pub kind: DiagnosticKind,
+}
+
+impl Diagnostic {
+    pub fn is_cascaded(&self) -> bool {
+        matches!(self.kind, DiagnosticKind::DependsOnFailed(_))
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: `{}`: ", self.pos, self.instruction)?;
+        match &self.kind {
+            DiagnosticKind::Error(err) => write!(f, "{}", err)?,
+            DiagnosticKind::DependsOnFailed(s) => write!(f, "depends on failed {}", s)?,
+        }
+        if let Some(s) = &self.symbol {
+            write!(f, " (discarding {})", s)?;
+        }
+        Ok(())
+    }
+}
+
+/// Execute parsed instructions, skipping the blocks that fail.
+///
+/// A block runs up to and including the next `def`, `hyp` or `obj`.
+/// When an instruction fails, the rest of its block is skipped, the state is
+/// rewound to where the block started and the exported symbol is marked as
+/// failed. Requiring a failed symbol later is reported as a cascade rather
+/// than as an error of its own. Once the time budget is used up, the rest of
+/// the program is skipped.
+pub fn execute_recovering<T: Recover + ?Sized>(
+    vm: &mut T,
+    prog: &[(Pos, Instruction)],
+) -> Vec<Diagnostic> {
+    let mut diags = Vec::new();
+    let mut failed = HashSet::new();
+    let mut start = vm.snapshot();
+    let mut i = 0;
+    while i < prog.len() {
+        let (pos, ins) = &prog[i];
+        let err = match ins.apply(vm) {
+            Ok(()) => {
+                if ins.export_name().is_some() {
+                    start = vm.snapshot();
+                }
+                i += 1;
+                continue;
+            }
+            Err(err) => err,
+        };
+        let end = prog[i..]
+            .iter()
+            .position(|(_, x)| x.export_name().is_some())
+            .map(|k| i + k);
+        let symbol = end.and_then(|k| prog[k].1.export_name()).map(String::from);
+        let out_of_time = matches!(
+            err,
+            OperationError::LimitExceeded {
+                limit: Limit::Time(_),
+                ..
+            }
+        );
+        let kind = match (ins, err) {
+            (Instruction::Req(s), OperationError::SymbolNotFound { .. }) if failed.contains(s) => {
+                DiagnosticKind::DependsOnFailed(s.clone())
+            }
+            (_, err) => DiagnosticKind::Error(err),
+        };
+        diags.push(Diagnostic {
+            pos: *pos,
+            instruction: ins.clone(),
+            symbol: symbol.clone(),
+            kind,
+        });
+        failed.extend(symbol);
+        if let Err(err) = vm.rewind(&start) {
+            // the state of the failed block cannot be undone
+            diags.push(Diagnostic {
+                pos: *pos,
+                instruction: ins.clone(),
+                symbol: None,
+                kind: DiagnosticKind::Error(err),
+            });
+            break;
+        }
+        i = match end {
+            Some(k) if !out_of_time => k + 1,
+            _ => prog.len(),
+        };
+    }
+    diags
+}
+
+/// Parse and execute a program, reporting every failed block.
+pub fn run_recovering<T: Recover + ?Sized>(
+    vm: &mut T,
+    src: &str,
+) -> std::result::Result<Vec<Diagnostic>, ParseError> {
+    Ok(execute_recovering(vm, &parse(src)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::Verifier;
+
+    #[test]
+    fn test_parse() {
+        let prog = parse("obj 2 foo # comment uni\n  syn arg 1\n\nreq sys::imply").unwrap();
+        let ins: Vec<_> = prog.iter().map(|(_, x)| x.to_string()).collect();
+        assert_eq!(ins, ["obj 2 foo", "syn", "arg 1", "req sys::imply"]);
+        assert_eq!(prog[2].0, Pos { line: 2, col: 7 });
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let e = parse("uni\n var  frob").unwrap_err();
+        assert_eq!(e.pos, Pos { line: 2, col: 7 });
+        assert_eq!(e.kind, ParseErrorKind::UnknownMnemonic("frob".into()));
+        let e = parse("syn arg 0").unwrap_err();
+        assert_eq!(e.pos, Pos { line: 1, col: 9 });
+        let e = parse("syn\nreq # nothing").unwrap_err();
+        assert_eq!(e.pos, Pos { line: 2, col: 1 });
+        assert_eq!(e.kind, ParseErrorKind::MissingOperand("symbol"));
+    }
+
+    #[test]
+    fn test_run() {
+        let mut vm: Verifier = Verifier::default();
+        let src = "
+            syn
+            uni var qed
+              req sys::l2
+                syn arg 1 app
+                syn req sys::imply syn arg 1 app syn arg 1 app app
+                syn arg 1 app
+              req sys::l1
+                syn arg 1 app
+                syn req sys::imply syn arg 1 app syn arg 1 app app
+              mp
+              req sys::l1 syn arg 1 app syn arg 1 app
+              mp
+            qed
+            hyp refl
+        ";
+        run(&mut vm, src).unwrap();
+        assert!(vm.has("refl"));
+        let src = "
+            syn
+            uni var var qed
+              req refl syn arg 1 app
+              req sys::l1 syn arg 1 app syn arg 2 app
+              mp
+            qed qed
+            hyp bad
+        ";
+        match run(&mut vm, src) {
+            Err(Error::Operation { pos, instruction, .. }) => {
+                assert_eq!(pos, Pos { line: 6, col: 15 });
+                assert_eq!(instruction, Instruction::Mp);
+            }
+            _ => panic!(),
+        }
+        match run(&mut vm, "req missing") {
+            Err(Error::Operation { pos, .. }) => assert_eq!(pos, Pos { line: 1, col: 1 }),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_recovering() {
+        let mut vm: Verifier = Verifier::default();
+        let src = "
+            syn uni var qed req missing qed hyp a
+            syn uni var qed req a syn arg 1 app qed hyp b
+            syn uni var qed arg 1 qed hyp c
+            syn uni var qed req c syn arg 1 app qed qed hyp d
+            syn uni var qed arg 1 qed hyp e
+        ";
+        let diags = run_recovering(&mut vm, src).unwrap();
+        assert_eq!(diags.len(), 3);
+        assert!(matches!(
+            &diags[0].kind,
+            DiagnosticKind::Error(OperationError::SymbolNotFound { .. })
+        ));
+        assert_eq!(diags[0].symbol.as_deref(), Some("a"));
+        assert!(diags[1].is_cascaded());
+        assert_eq!(diags[1].symbol.as_deref(), Some("b"));
+        assert!(!diags[2].is_cascaded());
+        assert_eq!(diags[2].pos.line, 5);
+        assert!(!vm.has("a") && !vm.has("b") && !vm.has("d"));
+        assert!(vm.has("c") && vm.has("e"));
+        assert!(run(&mut vm, "syn uni var qed arg 1 qed hyp f").is_ok());
+    }
+
+    #[test]
+    fn test_recover_popped() {
+        // the failed block drops an entry that was there before it started
+        let mut vm: Verifier = Verifier::default();
+        let diags = run_recovering(&mut vm, "req sys::l1 obj 0 p drop req nope def y").unwrap();
+        assert_eq!(diags.len(), 1);
+        assert_eq!(vm.show_stack().len(), 1);
+        assert!(run(&mut vm, "def l1_again").is_ok());
+    }
+}
@@ -0,0 +1,325 @@
+//! Hilbert-style proofs from natural-deduction-style ones.
+//!
+//! A [`Proof`] may introduce an assumption `p` with [`Proof::Hyp`] inside a
+//! [`Proof::Discharge`] of `p`. [`Deduction`] removes the assumptions one by
+//! one following the deduction theorem, so that the emitted proof only uses
+//! `app`, `mp`, `sys::l1`, `sys::l2` and the theorems the proof itself cites.
+//!
+//! Discharging `h` from a step proving `q`:
+//!
+//! - a step that does not use `h` is weakened by `l1: q -> (h -> q)`,
+//! - `h` itself becomes `h -> h`, proven from `l1` and `l2`,
+//! - `mp` on `p -> q` and `p` becomes `mp` on `l2: (h -> (p -> q)) ->
+//!   ((h -> p) -> (h -> q))` and the discharged premises.
+//!
+//! As `mp` needs synthetic mode, the result is exported as a `hyp`.
+
+use std::{collections::HashSet, error::Error as StdError, fmt, rc::Rc};
+
+use crate::{
+    err::Result,
+    isa::InstructionSet,
+    kit::{Expression, Prop, Scope},
+};
+
+/// `a -> (b -> a)`
+fn l1_concl(a: &Prop, b: &Prop) -> Prop {
+    a.imply(&b.imply(a))
+}
+
+/// `(a -> (b -> c)) -> ((a -> b) -> (a -> c))`
+fn l2_concl(a: &Prop, b: &Prop, c: &Prop) -> Prop {
+    a.imply(&b.imply(c)).imply(&a.imply(b).imply(&a.imply(c)))
+}
+
+/// `(!a -> !b) -> (b -> a)`
+fn l3_concl(a: &Prop, b: &Prop) -> Prop {
+    a.not().imply(&b.not()).imply(&b.imply(a))
+}
+
+pub enum Proof {
+    /// The assumption `p`, which must be discharged by an enclosing
+    /// [`Proof::Discharge`] of `p`
+    Hyp(Prop),
+    /// The theorem `name` instantiated with `args`, which proves `concl`
+    Thm {
+        name: Rc<str>,
+        args: Vec<Prop>,
+        concl: Prop,
+    },
+    /// `q` from the proofs of `p -> q` and `p`
+    Mp(Box<Proof>, Box<Proof>),
+    /// `p -> q` from a proof of `q` that may assume `p`
+    Discharge(Prop, Box<Proof>),
+}
+
+impl Proof {
+    pub fn hyp(p: &Prop) -> Self {
+        Proof::Hyp(p.clone())
+    }
+
+    pub fn thm(name: &str, args: Vec<Prop>, concl: Prop) -> Self {
+        Proof::Thm {
+            name: name.into(),
+            args,
+            concl,
+        }
+    }
+
+    pub fn l1(a: &Prop, b: &Prop) -> Self {
+        Self::thm("sys::l1", vec![a.clone(), b.clone()], l1_concl(a, b))
+    }
+
+    pub fn l2(a: &Prop, b: &Prop, c: &Prop) -> Self {
+        let args = vec![a.clone(), b.clone(), c.clone()];
+        Self::thm("sys::l2", args, l2_concl(a, b, c))
+    }
+
+    pub fn l3(a: &Prop, b: &Prop) -> Self {
+        Self::thm("sys::l3", vec![a.clone(), b.clone()], l3_concl(a, b))
+    }
+
+    /// Modus ponens, with `self` proving `p -> q` and `p` proving `p`
+    pub fn mp(self, p: Proof) -> Self {
+        Proof::Mp(Box::new(self), Box::new(p))
+    }
+
+    pub fn discharge(p: &Prop, body: Proof) -> Self {
+        Proof::Discharge(p.clone(), Box::new(body))
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// An assumption outside of any discharge of it
+    Unbound(Prop),
+    /// The first premise of `mp` is not an implication
+    NotImplication(Prop),
+    /// The second premise of `mp` does not match the condition of the first
+    Mismatch { expected: Prop, actual: Prop },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Unbound(p) => write!(f, "Assumption {} is never discharged", p),
+            Error::NotImplication(p) => write!(f, "Expected an implication, found {}", p),
+            Error::Mismatch { expected, actual } => {
+                write!(
+                    f,
+                    "Condition not met: expected {}, found {}",
+                    expected, actual
+                )
+            }
+        }
+    }
+}
+
+impl StdError for Error {}
+
+enum Step {
+    Hyp,
+    Thm { name: Rc<str>, args: Vec<Prop> },
+    Mp(Rc<Line>, Rc<Line>),
+}
+
+/// A step of a proof together with what it proves and the assumptions it
+/// still depends on
+struct Line {
+    concl: Prop,
+    hyps: HashSet<Prop>,
+    step: Step,
+}
+
+impl Line {
+    fn thm(name: &str, args: Vec<Prop>, concl: Prop) -> Rc<Self> {
+        Rc::new(Line {
+            concl,
+            hyps: HashSet::new(),
+            step: Step::Thm {
+                name: name.into(),
+                args,
+            },
+        })
+    }
+
+    /// `imp` must prove an implication whose condition `p` proves
+    fn mp(imp: Rc<Line>, p: Rc<Line>) -> Rc<Self> {
+        let (_, q) = imp.concl.split_imply().expect("checked by the caller");
+        Rc::new(Line {
+            concl: q.clone(),
+            hyps: imp.hyps.union(&p.hyps).cloned().collect(),
+            step: Step::Mp(imp, p),
+        })
+    }
+
+    /// A line proving `h -> self.concl` without assuming `h`
+    fn discharge(self: &Rc<Self>, h: &Prop) -> Rc<Self> {
+        let q = &self.concl;
+        if !self.hyps.contains(h) {
+            let l1 = Line::thm("sys::l1", vec![q.clone(), h.clone()], l1_concl(q, h));
+            return Line::mp(l1, self.clone());
+        }
+        match &self.step {
+            Step::Hyp => {
+                let hh = h.imply(h);
+                let args = vec![h.clone(), hh.clone(), h.clone()];
+                let l2 = Line::thm("sys::l2", args, l2_concl(h, &hh, h));
+                let l1 = Line::thm("sys::l1", vec![h.clone(), hh.clone()], l1_concl(h, &hh));
+                let l1h = Line::thm("sys::l1", vec![h.clone(), h.clone()], l1_concl(h, h));
+                Line::mp(Line::mp(l2, l1), l1h)
+            }
+            Step::Mp(imp, p) => {
+                let args = vec![h.clone(), p.concl.clone(), q.clone()];
+                let l2 = Line::thm("sys::l2", args, l2_concl(h, &p.concl, q));
+                Line::mp(Line::mp(l2, imp.discharge(h)), p.discharge(h))
+            }
+            Step::Thm { .. } => unreachable!("theorems assume nothing"),
+        }
+    }
+
+    fn compile(proof: &Proof, scope: &mut Vec<Prop>) -> std::result::Result<Rc<Self>, Error> {
+        match proof {
+            Proof::Hyp(p) => {
+                if !scope.contains(p) {
+                    return Err(Error::Unbound(p.clone()));
+                }
+                Ok(Rc::new(Line {
+                    concl: p.clone(),
+                    hyps: HashSet::from([p.clone()]),
+                    step: Step::Hyp,
+                }))
+            }
+            Proof::Thm { name, args, concl } => Ok(Line::thm(name, args.clone(), concl.clone())),
+            Proof::Mp(imp, p) => {
+                let imp = Self::compile(imp, scope)?;
+                let p = Self::compile(p, scope)?;
+                let Some((cond, _)) = imp.concl.split_imply() else {
+                    return Err(Error::NotImplication(imp.concl.clone()));
+                };
+                if *cond != p.concl {
+                    return Err(Error::Mismatch {
+                        expected: cond.clone(),
+                        actual: p.concl.clone(),
+                    });
+                }
+                Ok(Line::mp(imp, p))
+            }
+            Proof::Discharge(h, body) => {
+                scope.push(h.clone());
+                let body = Self::compile(body, scope);
+                scope.pop();
+                Ok(body?.discharge(h))
+            }
+        }
+    }
+
+    fn add_to<T: InstructionSet + ?Sized>(&self, vm: &mut T, scope: &mut Scope) -> Result<()> {
+        match &self.step {
+            Step::Hyp => unreachable!("every assumption is discharged"),
+            Step::Thm { name, args } => {
+                vm.req(name)?;
+                for arg in args {
+                    vm.syn()?;
+                    arg.add_scoped(vm, scope)?;
+                    vm.app()?;
+                }
+                Ok(())
+            }
+            Step::Mp(imp, p) => {
+                imp.add_to(vm, scope)?;
+                p.add_to(vm, scope)?;
+                vm.mp()
+            }
+        }
+    }
+}
+
+/// A [`Proof`] compiled to `mp` and `app` on theorems, which can be emitted as
+/// an [`Expression`]
+pub struct Deduction {
+    line: Rc<Line>,
+}
+
+impl Deduction {
+    pub fn new(proof: &Proof) -> std::result::Result<Self, Error> {
+        let line = Line::compile(proof, &mut Vec::new())?;
+        Ok(Self { line })
+    }
+
+    /// What the proof proves
+    pub fn conclusion(&self) -> &Prop {
+        &self.line.concl
+    }
+}
+
+impl<T: InstructionSet + ?Sized> Expression<T> for Deduction {
+    fn add_scoped(&self, vm: &mut T, scope: &mut Scope) -> Result<()> {
+        self.line.add_to(vm, scope)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{kit::Forall, vm::Verifier};
+
+    #[test]
+    fn test_deduce() {
+        let mut vm: Verifier = Verifier::default();
+        // (a -> b) -> (b -> c) -> a -> c
+        Forall::new(3, |v| {
+            let [a, b, c]: [Prop; 3] = [v[0].into(), v[1].into(), v[2].into()];
+            let (ab, bc) = (a.imply(&b), b.imply(&c));
+            let body = Proof::hyp(&bc).mp(Proof::hyp(&ab).mp(Proof::hyp(&a)));
+            let proof = Proof::discharge(&ab, Proof::discharge(&bc, Proof::discharge(&a, body)));
+            let ded = Deduction::new(&proof).unwrap();
+            assert_eq!(*ded.conclusion(), ab.imply(&bc.imply(&a.imply(&c))));
+            Box::new(ded)
+        })
+        .export(&mut vm, "chain".into(), false)
+        .unwrap();
+        assert_eq!(
+            vm.show("chain").unwrap(),
+            "∀a. ∀b. ∀c. sys::imply(sys::imply(a, b), sys::imply(sys::imply(b, c), sys::imply(a, c)))"
+        );
+
+        // a -> !a -> b, citing l1 and l3 under the assumptions
+        Forall::new(2, |v| {
+            let [a, b]: [Prop; 2] = [v[0].into(), v[1].into()];
+            let nb_na = Proof::l1(&a.not(), &b.not()).mp(Proof::hyp(&a.not()));
+            let body = Proof::l3(&b, &a).mp(nb_na).mp(Proof::hyp(&a));
+            let proof = Proof::discharge(&a, Proof::discharge(&a.not(), body));
+            Box::new(Deduction::new(&proof).unwrap())
+        })
+        .export(&mut vm, "efq".into(), false)
+        .unwrap();
+        assert_eq!(
+            vm.show("efq").unwrap(),
+            "∀a. ∀b. sys::imply(a, sys::imply(sys::not(a), b))"
+        );
+        assert!(vm.assumptions("efq").unwrap().iter().all(|x| match x {
+            crate::vm::Assumption::Hypothesis(h) => h == "efq",
+            x => !matches!(x, crate::vm::Assumption::Sat(_)),
+        }));
+    }
+
+    #[test]
+    fn test_errors() {
+        let (a, b) = (Prop::atom("a"), Prop::atom("b"));
+        assert!(matches!(
+            Deduction::new(&Proof::hyp(&a)),
+            Err(Error::Unbound(p)) if p == a
+        ));
+        let proof = Proof::discharge(&a, Proof::hyp(&a).mp(Proof::hyp(&a)));
+        assert!(matches!(
+            Deduction::new(&proof),
+            Err(Error::NotImplication(p)) if p == a
+        ));
+        let proof = Proof::discharge(&a, Proof::l1(&b, &b).mp(Proof::hyp(&a)));
+        assert!(matches!(
+            Deduction::new(&proof),
+            Err(Error::Mismatch { expected, actual }) if expected == b && actual == a
+        ));
+    }
+}
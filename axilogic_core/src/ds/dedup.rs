@@ -1,7 +1,7 @@
 use std::{
     cell::RefCell,
     collections::HashSet,
-    hash::{BuildHasher, Hash, Hasher},
+    hash::{BuildHasher, Hash},
     ops::Deref,
     rc::{Rc, Weak},
 };
@@ -42,6 +42,10 @@ impl<K: Eq + Hash> Hash for HashDedupPtr<K> {
 }
 impl<K: Eq + Hash> Drop for HashDedupPtr<K> {
     fn drop(&mut self) {
+        // The registry holds one reference, and this pointer is the last other one
+        if Rc::strong_count(&self.data) != 2 {
+            return;
+        }
         if let Some(registry) = self.data.registry.upgrade() {
             registry.borrow_mut().remove(&HashDedupEntry {
                 data: self.data.clone(),
@@ -51,7 +55,7 @@ impl<K: Eq + Hash> Drop for HashDedupPtr<K> {
 }
 impl<K: Eq + Hash> PartialOrd for HashDedupPtr<K> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(Rc::as_ptr(&self.data).cmp(&Rc::as_ptr(&other.data)))
+        Some(self.cmp(other))
     }
 }
 impl<K: Eq + Hash> Ord for HashDedupPtr<K> {
@@ -93,6 +97,11 @@ impl<K: Eq + Hash> HashDedup<K> {
         }
     }
 }
+impl<K: Eq + Hash> Default for HashDedup<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl<K: Eq + Hash> Deref for HashDedupPtr<K> {
     type Target = K;
     fn deref(&self) -> &Self::Target {
@@ -104,11 +113,7 @@ impl<K: Eq + Hash> Dedup for HashDedup<K> {
     type Key = K;
     fn get(&mut self, key: Self::Key) -> Self::Ptr {
         let mut map = self.map.borrow_mut();
-        let hash = {
-            let mut hasher = map.hasher().build_hasher();
-            key.hash(&mut hasher);
-            hasher.finish()
-        };
+        let hash = map.hasher().hash_one(&key);
         let entry = HashDedupEntry {
             data: Rc::new(HashDedupNode {
                 key,
@@ -116,12 +121,43 @@ impl<K: Eq + Hash> Dedup for HashDedup<K> {
                 registry: Rc::downgrade(&self.map),
             }),
         };
-        HashDedupPtr {
-            data: if map.insert(entry.clone()) {
-                entry.data
-            } else {
-                map.get(&entry).unwrap().data.clone()
-            },
-        }
+        let data = if map.insert(entry.clone()) {
+            entry.data
+        } else {
+            let data = map.get(&entry).unwrap().data.clone();
+            // Dropping the duplicated key may release pointers into the registry
+            std::mem::drop(map);
+            data
+        };
+        HashDedupPtr { data }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(PartialEq, Eq, Hash)]
+    enum Tree {
+        Leaf,
+        Node(HashDedupPtr<Tree>, HashDedupPtr<Tree>),
+    }
+
+    #[test]
+    fn test_dedup() {
+        let mut dedup = HashDedup::new();
+        let leaf = dedup.get(Tree::Leaf);
+        let a = dedup.get(Tree::Node(leaf.clone(), leaf.clone()));
+        // the duplicated key holds pointers into the registry, dropped while
+        // looking it up
+        let b = dedup.get(Tree::Node(leaf.clone(), leaf.clone()));
+        assert!(a == b);
+
+        // an entry stays while any pointer to it is alive
+        drop(b);
+        let c = dedup.get(Tree::Node(leaf.clone(), leaf.clone()));
+        assert!(a == c);
+        drop((a, c));
+        assert_eq!(dedup.map.borrow().len(), 1);
     }
 }
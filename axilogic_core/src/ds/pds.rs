@@ -44,7 +44,7 @@ fn set_diff1<T: Hash + Eq>(a: &mut HashTrieSet<T>, b: &HashTrieSet<T>) {
 }
 
 fn set_diff2<T: Clone + Hash + Eq>(a: &HashTrieSet<T>, b: &HashTrieSet<T>) -> HashTrieSet<T> {
-    a.iter().filter(|&x| !b.contains(x)).map(|x| x.clone()).collect()
+    a.iter().filter(|&x| !b.contains(x)).cloned().collect()
 }
 
 pub fn set_diff_mut<T: Clone + Hash + Eq>(a: &mut HashTrieSet<T>, b: &HashTrieSet<T>) {
@@ -1,6 +1,6 @@
 use std::{cell::RefCell, rc::Rc};
 
-#[derive(Eq, Ord)]
+#[derive(Eq)]
 pub struct Info<T: Ord> {
     key: RefCell<usize>,
     value: T,
@@ -14,7 +14,13 @@ impl<T: Ord> PartialEq for Info<T> {
 
 impl<T: Ord> PartialOrd for Info<T> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.key.partial_cmp(&other.key)
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> Ord for Info<T> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key.cmp(&other.key)
     }
 }
 
@@ -40,12 +46,21 @@ pub struct Tree<T: Ord> {
     root: Option<Box<Node<T>>>,
 }
 
+impl<T: Ord> Default for Tree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: Ord> Tree<T> {
     pub fn new() -> Self {
         Tree { root: None }
     }
     pub fn len(&self) -> usize {
-        return self.root.as_ref().map_or(0, |v| v.size);
+        self.root.as_ref().map_or(0, |v| v.size)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
     }
     fn mid_point(l: usize, r: usize) -> usize {
         l + (r - l) / 2
@@ -120,6 +135,7 @@ impl<T: Ord> Tree<T> {
     pub fn add(&mut self, value: T) -> Rc<Info<T>> {
         self.insert_node(value, (0, usize::MAX), false)
     }
+    #[cfg(test)]
     fn calc_height(&self) -> usize {
         if let Some(v) = self.root.as_ref() {
             1 + std::cmp::max(v.left.calc_height(), v.right.calc_height())
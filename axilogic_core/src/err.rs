@@ -1,28 +1,215 @@
-use std::{error::Error, fmt};
+use std::{error::Error, fmt, num::NonZeroUsize};
 
-#[derive(Debug)]
-pub struct OperationError {
-    details: String,
+use crate::vm::{Limit, Type};
+
+/// Kind of an entry on the verifier stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackKind {
+    Argument,
+    Synthetic,
+    Types,
+    Element,
 }
 
-impl OperationError {
-    pub fn new<T: Into<String>>(msg: T) -> OperationError {
-        OperationError {
-            details: msg.into(),
+impl fmt::Display for StackKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StackKind::Argument => write!(f, "argument"),
+            StackKind::Synthetic => write!(f, "syn"),
+            StackKind::Types => write!(f, "types"),
+            StackKind::Element => write!(f, "element"),
         }
     }
 }
 
+/// A `depth` is the number of entries on the stack when the failure occurred,
+/// and a `position` is that of the offending entry, counted from 1 at the
+/// bottom of the stack.
+#[derive(Debug)]
+pub enum OperationError {
+    StackUnderflow {
+        depth: usize,
+    },
+    ExpectedElement {
+        position: usize,
+        found: StackKind,
+    },
+    ExpectedTypes {
+        depth: usize,
+        found: Option<StackKind>,
+    },
+    /// `hkt` with fewer than two types declared by the `uni` on top
+    TooFewTypes {
+        found: usize,
+    },
+    /// Exporting or applying without the matching `syn`
+    ExpectedSyntheticMarker {
+        position: usize,
+        found: StackKind,
+    },
+    ExpectedSyntheticMode,
+    ExpectedRealMode {
+        syn_depth: usize,
+    },
+    NotImplication {
+        found: String,
+    },
+    CannotApply {
+        func: Type,
+        arg: Type,
+    },
+    TypeMismatch {
+        expected: Type,
+        actual: Type,
+    },
+    UnboundedExport {
+        name: String,
+        max_ref: usize,
+    },
+    SymbolExists {
+        name: String,
+    },
+    SymbolNotFound {
+        name: String,
+    },
+    ImaginarySymbol {
+        name: String,
+    },
+    /// `depth` is the number of arguments in scope
+    ArgumentOutOfRange {
+        index: NonZeroUsize,
+        depth: usize,
+    },
+    /// Picking an element from below a `syn` or `uni`
+    CrossingMarker {
+        position: usize,
+        found: StackKind,
+    },
+    QedWithoutUni {
+        found: StackKind,
+    },
+    QedWithoutArgument {
+        found: StackKind,
+    },
+    ConditionNotMet {
+        expected: String,
+        actual: String,
+    },
+    /// A [`crate::kit::ForallArg`] emitted outside of its `Forall`
+    UnboundVariable {
+        var: usize,
+    },
+    /// A [`crate::kit::Forall`] that binds no variable
+    EmptyForall,
+    /// A [`crate::kernel::Kernel`] declares its implication or negation with
+    /// the wrong number of arguments
+    KernelArity {
+        name: String,
+        expected: usize,
+        actual: usize,
+    },
+    /// Restoring a [`crate::vm::Checkpoint`] taken from another verifier
+    ForeignCheckpoint,
+    /// A bound set by [`crate::vm::Verifier::set_limits`] was reached
+    LimitExceeded {
+        instruction: &'static str,
+        limit: Limit,
+    },
+}
+
 impl fmt::Display for OperationError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "OperationError: {}", self.details)
+        use OperationError::*;
+        match self {
+            StackUnderflow { depth } => write!(f, "Stack underflow at depth {}", depth),
+            ExpectedElement { position, found } => write!(
+                f,
+                "Expected element, found {} at position {} from the bottom",
+                found, position
+            ),
+            ExpectedTypes { depth, found } => match found {
+                Some(found) => write!(
+                    f,
+                    "Expected types on stack top, found {} at depth {}",
+                    found, depth
+                ),
+                None => write!(f, "Expected types on stack top, found empty stack"),
+            },
+            TooFewTypes { found } => write!(f, "hkt needs two types, found {}", found),
+            ExpectedSyntheticMarker { position, found } => write!(
+                f,
+                "Expected syn below the element, found {} at position {} from the bottom",
+                found, position
+            ),
+            ExpectedSyntheticMode => write!(f, "Expected synthetic mode"),
+            ExpectedRealMode { syn_depth } => write!(
+                f,
+                "Expected non-synthetic mode, inside {} syn block(s)",
+                syn_depth
+            ),
+            NotImplication { found } => write!(f, "Expected an implication, found {}", found),
+            CannotApply { func, arg } => {
+                write!(f, "Cannot apply type {} to {}", func, arg)
+            }
+            TypeMismatch { expected, actual } => write!(
+                f,
+                "Type mismatch for application: expected {}, found {}",
+                expected, actual
+            ),
+            UnboundedExport { name, max_ref } => write!(
+                f,
+                "Exporting an unbounded element as {}: refers to argument {}",
+                name, max_ref
+            ),
+            SymbolExists { name } => write!(f, "Symbol already exists: {}", name),
+            SymbolNotFound { name } => write!(f, "Symbol not found: {}", name),
+            ImaginarySymbol { name } => write!(
+                f,
+                "Using imaginary symbol {} in non-synthetic mode",
+                name
+            ),
+            ArgumentOutOfRange { index, depth } => write!(
+                f,
+                "Argument index out of range: {} with {} argument(s)",
+                index, depth
+            ),
+            CrossingMarker { position, found } => write!(
+                f,
+                "Cannot pick an element across {} at position {} from the bottom",
+                found, position
+            ),
+            QedWithoutUni { found } => write!(f, "Calling qed on {} without uni", found),
+            QedWithoutArgument { found } => write!(
+                f,
+                "End of proof without an argument, found {}",
+                found
+            ),
+            ConditionNotMet { expected, actual } => write!(
+                f,
+                "Using mp but condition not met: expected {}, found {}",
+                expected, actual
+            ),
+            UnboundVariable { var } => {
+                write!(f, "Variable ${} used outside of its Forall", var)
+            }
+            EmptyForall => write!(f, "Forall must bind a variable"),
+            KernelArity {
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Kernel object {} must take {} argument(s), declared with {}",
+                name, expected, actual
+            ),
+            ForeignCheckpoint => write!(f, "Restoring a checkpoint of another verifier"),
+            LimitExceeded { instruction, limit } => {
+                write!(f, "Limit exceeded by {}: {}", instruction, limit)
+            }
+        }
     }
 }
 
-impl Error for OperationError {
-    fn description(&self) -> &str {
-        &self.details
-    }
-}
+impl Error for OperationError {}
 
 pub type Result<T> = std::result::Result<T, OperationError>;
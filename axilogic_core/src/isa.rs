@@ -2,6 +2,8 @@ use std::num::NonZeroUsize;
 
 use crate::err::Result;
 
+/// An instruction that fails must leave the state exactly as it was,
+/// so that the caller can recover and continue.
 pub trait InstructionSet {
     /// Universal quantification
     fn uni(&mut self) -> Result<()>;
@@ -29,6 +31,25 @@ pub trait InstructionSet {
     fn sat(&mut self) -> Result<()>;
     fn arg(&mut self, n: NonZeroUsize) -> Result<()>;
 
-    // Helper (not part of the assembly)
+    /// Stack, on elements only; `pick` may reach below `Argument` markers
+    /// but not below `syn` or `uni`
+    fn dup(&mut self) -> Result<()>; // [..., x] => [..., x, x]
+    fn pick(&mut self, n: NonZeroUsize) -> Result<()>; // [..., x, ...(n-1)] => [..., x, ...(n-1), x]
+    fn swap(&mut self) -> Result<()>; // [..., x, y] => [..., y, x]
+    fn drop(&mut self) -> Result<()>; // [..., x] => [...]
+
+    // Helpers (not part of the assembly)
     fn has(&self, s: &str) -> bool;
+    /// Number of entries on the stack
+    fn depth(&self) -> usize;
+}
+
+/// Instruction sets that can abandon a partially executed block
+pub trait Recover: InstructionSet {
+    /// The state a block starts from
+    type Snapshot;
+    fn snapshot(&self) -> Self::Snapshot;
+    /// Go back to `snapshot`, including the entries the block popped.
+    /// Fails if `snapshot` was taken from another instance.
+    fn rewind(&mut self, snapshot: &Self::Snapshot) -> Result<()>;
 }
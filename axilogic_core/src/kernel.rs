@@ -0,0 +1,189 @@
+//! Primitive objects and axioms a [`Verifier`](crate::vm::Verifier) starts with.
+
+use crate::{
+    isa::InstructionSet,
+    kit::{imply, not, Forall, Ptr},
+};
+
+pub trait Kernel {
+    /// Primitive objects as `(arity, name)`, declared in order
+    fn objects(&self) -> Vec<(usize, &'static str)>;
+
+    /// The binary object used by `mp` and `sat`, if any
+    fn imply(&self) -> Option<&'static str>;
+
+    /// The unary object read as negation by the truth-table check, if any
+    fn not(&self) -> Option<&'static str>;
+
+    /// Axioms are exported with `hyp` and then made real, in order
+    fn axioms<'a, T: InstructionSet + 'a>(&self) -> Vec<(&'static str, Ptr<'a, T>)>;
+}
+
+const PROPOSITIONAL: [(usize, &str); 2] = [(1, "sys::not"), (2, "sys::imply")];
+
+/// `a -> (b -> a)`
+pub fn l1<'a, T: InstructionSet + 'a>() -> Ptr<'a, T> {
+    Box::new(Forall::new(2, |args| {
+        let a = args[0];
+        let b = args[1];
+        imply(a.into(), imply(b.into(), a.into()))
+    }))
+}
+
+/// `(a -> (b -> c)) -> ((a -> b) -> (a -> c))`
+pub fn l2<'a, T: InstructionSet + 'a>() -> Ptr<'a, T> {
+    Box::new(Forall::new(3, |args| {
+        let a = args[0];
+        let b = args[1];
+        let c = args[2];
+        imply(
+            imply(a.into(), imply(b.into(), c.into())),
+            imply(imply(a.into(), b.into()), imply(a.into(), c.into())),
+        )
+    }))
+}
+
+/// `(!a -> !b) -> (b -> a)`
+pub fn l3<'a, T: InstructionSet + 'a>() -> Ptr<'a, T> {
+    Box::new(Forall::new(2, |args| {
+        let a = args[0];
+        let b = args[1];
+        imply(
+            imply(not(a.into()), not(b.into())),
+            imply(b.into(), a.into()),
+        )
+    }))
+}
+
+/// Łukasiewicz's axioms `sys::l1`, `sys::l2` and `sys::l3`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Classical;
+
+impl Kernel for Classical {
+    fn objects(&self) -> Vec<(usize, &'static str)> {
+        PROPOSITIONAL.to_vec()
+    }
+
+    fn imply(&self) -> Option<&'static str> {
+        Some("sys::imply")
+    }
+
+    fn not(&self) -> Option<&'static str> {
+        Some("sys::not")
+    }
+
+    fn axioms<'a, T: InstructionSet + 'a>(&self) -> Vec<(&'static str, Ptr<'a, T>)> {
+        vec![("sys::l1", l1()), ("sys::l2", l2()), ("sys::l3", l3())]
+    }
+}
+
+/// The classical kernel without contraposition `sys::l3`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Intuitionistic;
+
+impl Kernel for Intuitionistic {
+    fn objects(&self) -> Vec<(usize, &'static str)> {
+        PROPOSITIONAL.to_vec()
+    }
+
+    fn imply(&self) -> Option<&'static str> {
+        Some("sys::imply")
+    }
+
+    fn not(&self) -> Option<&'static str> {
+        Some("sys::not")
+    }
+
+    fn axioms<'a, T: InstructionSet + 'a>(&self) -> Vec<(&'static str, Ptr<'a, T>)> {
+        vec![("sys::l1", l1()), ("sys::l2", l2())]
+    }
+}
+
+/// No objects and no axioms; `mp` and `sat` always fail
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Empty;
+
+impl Kernel for Empty {
+    fn objects(&self) -> Vec<(usize, &'static str)> {
+        Vec::new()
+    }
+
+    fn imply(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn not(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn axioms<'a, T: InstructionSet + 'a>(&self) -> Vec<(&'static str, Ptr<'a, T>)> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asm, err::OperationError, vm::Verifier};
+
+    const CONTRAPOSE: &str = "
+        syn uni var qed
+          req sys::l3 syn arg 1 app syn arg 1 app
+        qed hyp contra
+    ";
+
+    #[test]
+    fn test_kernels() {
+        let mut vm: Verifier = Verifier::with_kernel(Default::default(), &Intuitionistic).unwrap();
+        assert!(vm.has("sys::l1") && vm.has("sys::l2") && !vm.has("sys::l3"));
+        assert!(asm::run(&mut vm, CONTRAPOSE).is_err());
+        let mut vm: Verifier = Verifier::with_kernel(Default::default(), &Classical).unwrap();
+        asm::run(&mut vm, CONTRAPOSE).unwrap();
+
+        let mut vm: Verifier = Verifier::with_kernel(Default::default(), &Empty).unwrap();
+        assert!(!vm.has("sys::imply"));
+        asm::run(
+            &mut vm,
+            "obj 2 imp obj 0 p syn req imp syn req p app syn req p app req p",
+        )
+        .unwrap();
+        match vm.mp() {
+            Err(OperationError::NotImplication { found }) => assert_eq!(found, "imp(p, p)"),
+            _ => panic!(),
+        }
+    }
+
+    /// A kernel whose implication takes a single argument
+    struct UnaryImply;
+
+    impl Kernel for UnaryImply {
+        fn objects(&self) -> Vec<(usize, &'static str)> {
+            vec![(1, "imp")]
+        }
+
+        fn imply(&self) -> Option<&'static str> {
+            Some("imp")
+        }
+
+        fn not(&self) -> Option<&'static str> {
+            None
+        }
+
+        fn axioms<'a, T: InstructionSet + 'a>(&self) -> Vec<(&'static str, Ptr<'a, T>)> {
+            Vec::new()
+        }
+    }
+
+    #[test]
+    fn test_kernel_arity() {
+        let vm: Result<Verifier, _> = Verifier::with_kernel(Default::default(), &UnaryImply);
+        match vm {
+            Err(OperationError::KernelArity {
+                name,
+                expected,
+                actual,
+            }) => assert_eq!((name.as_str(), expected, actual), ("imp", 2, 1)),
+            _ => panic!(),
+        }
+    }
+}
@@ -1,9 +1,44 @@
-use std::num::NonZeroUsize;
+use std::{fmt, num::NonZeroUsize, rc::Rc};
 
-use crate::{err::Result, isa::InstructionSet};
+use crate::{
+    err::{OperationError, Result},
+    isa::InstructionSet,
+};
 
-pub trait Expression<T: InstructionSet> {
-    fn add_to(&self, vm: &mut T) -> Result<()>;
+/// The variables of the enclosing [`Forall`]s while an expression is emitted,
+/// innermost last
+#[derive(Debug, Default)]
+pub struct Scope {
+    vars: Vec<ForallArg>,
+    /// Variables created so far
+    next: usize,
+}
+
+impl Scope {
+    /// A variable distinct from every other one of this scope
+    fn fresh(&mut self) -> ForallArg {
+        self.next += 1;
+        ForallArg(self.next - 1)
+    }
+
+    /// The argument index of `var`, relative to the innermost binder
+    fn index(&self, var: ForallArg) -> Result<NonZeroUsize> {
+        let pos = self
+            .vars
+            .iter()
+            .rposition(|&x| x == var)
+            .ok_or(OperationError::UnboundVariable { var: var.0 })?;
+        Ok(NonZeroUsize::new(self.vars.len() - pos).unwrap())
+    }
+}
+
+pub trait Expression<T: InstructionSet + ?Sized> {
+    /// Emit the expression inside the binders of `scope`
+    fn add_scoped(&self, vm: &mut T, scope: &mut Scope) -> Result<()>;
+
+    fn add_to(&self, vm: &mut T) -> Result<()> {
+        self.add_scoped(vm, &mut Scope::default())
+    }
     fn export(&self, vm: &mut T, name: String, is_real: bool) -> Result<()> {
         if !is_real {
             vm.syn()?;
@@ -17,77 +52,499 @@ pub trait Expression<T: InstructionSet> {
         Ok(())
     }
 }
-type Ptr<'a, T> = Box<dyn Expression<T> + 'a>;
+pub type Ptr<'a, T> = Box<dyn Expression<T> + 'a>;
 
-#[derive(Debug, Clone, Copy)]
-pub struct ForallArg(NonZeroUsize);
-impl<T: InstructionSet> Expression<T> for ForallArg {
-    fn add_to(&self, vm: &mut T) -> Result<()> {
-        vm.arg(self.0)?;
+/// A variable bound by a [`Forall`], which can be captured by the
+/// expressions nested inside it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ForallArg(usize);
+impl ForallArg {
+    /// The variable applied to `args`, for a variable of a function type
+    pub fn apply<'a, T: InstructionSet + ?Sized + 'a>(self, args: Vec<Ptr<'a, T>>) -> Ptr<'a, T> {
+        Box::new(Apply::new(self.into(), args))
+    }
+}
+impl<T: InstructionSet + ?Sized> Expression<T> for ForallArg {
+    fn add_scoped(&self, vm: &mut T, scope: &mut Scope) -> Result<()> {
+        vm.arg(scope.index(*self)?)?;
         Ok(())
     }
 }
-impl<'a, T: InstructionSet> From<ForallArg> for Ptr<'a, T> {
+impl<'a, T: InstructionSet + ?Sized> From<ForallArg> for Ptr<'a, T> {
     fn from(arg: ForallArg) -> Self {
         Box::new(arg)
     }
 }
 
-/// Does not support nesting!
-pub struct Forall<'a, T: InstructionSet> {
-    num_vars: NonZeroUsize,
-    expr: Ptr<'a, T>,
+/// The type of a variable bound by a [`Forall`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VarType {
+    /// `@`
+    Symbol,
+    /// `p=>q`
+    Function(Box<VarType>, Box<VarType>),
 }
-impl<'a, T: InstructionSet> Forall<'a, T> {
-    pub fn new<F: Fn(Vec<ForallArg>) -> Ptr<'a, T>>(num_vars: usize, f: F) -> Self {
+impl VarType {
+    pub fn func(p: VarType, q: VarType) -> Self {
+        Self::Function(Box::new(p), Box::new(q))
+    }
+
+    /// Declare the type inside a `uni`
+    fn add_to<T: InstructionSet + ?Sized>(&self, vm: &mut T) -> Result<()> {
+        match self {
+            VarType::Symbol => vm.var(),
+            VarType::Function(p, q) => {
+                p.add_to(vm)?;
+                q.add_to(vm)?;
+                vm.hkt()
+            }
+        }
+    }
+}
+/// Written like [`crate::vm::Type`]
+impl fmt::Display for VarType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VarType::Symbol => write!(f, "@"),
+            VarType::Function(p, q) if matches!(**p, VarType::Function(..)) => {
+                write!(f, "({})=>{}", p, q)
+            }
+            VarType::Function(p, q) => write!(f, "{}=>{}", p, q),
+        }
+    }
+}
+
+/// Binds `num_vars` variables, outermost first.
+///
+/// `Forall`s can be nested, and a body may use the variables of any enclosing
+/// `Forall` captured by a `move` closure. The body is built each time the
+/// expression is emitted, from variables that are fresh in the [`Scope`], and
+/// the argument indices are worked out then.
+pub struct Forall<'a, T: InstructionSet + ?Sized> {
+    types: Vec<VarType>,
+    body: Box<dyn Fn(Vec<ForallArg>) -> Ptr<'a, T> + 'a>,
+}
+impl<'a, T: InstructionSet + ?Sized> Forall<'a, T> {
+    /// Variables of the symbol type `@`
+    pub fn new<F>(num_vars: usize, f: F) -> Self
+    where
+        F: Fn(Vec<ForallArg>) -> Ptr<'a, T> + 'a,
+    {
+        Self::typed(vec![VarType::Symbol; num_vars], f)
+    }
+
+    /// One variable for each type in `types`, which must not be empty
+    pub fn typed<F>(types: Vec<VarType>, f: F) -> Self
+    where
+        F: Fn(Vec<ForallArg>) -> Ptr<'a, T> + 'a,
+    {
         Self {
-            num_vars: num_vars.try_into().unwrap(),
-            expr: f((1..=num_vars)
-                .rev()
-                .map(|i| ForallArg(NonZeroUsize::new(i).unwrap()))
-                .collect()),
+            types,
+            body: Box::new(f),
         }
     }
 }
 
-impl<'a, T: InstructionSet> Expression<T> for Forall<'a, T> {
-    fn add_to(&self, vm: &mut T) -> Result<()> {
+impl<'a, T: InstructionSet + ?Sized> Expression<T> for Forall<'a, T> {
+    fn add_scoped(&self, vm: &mut T, scope: &mut Scope) -> Result<()> {
+        if self.types.is_empty() {
+            return Err(OperationError::EmptyForall);
+        }
         vm.uni()?;
-        for _ in 0..self.num_vars.get() {
-            vm.var()?;
+        for ty in &self.types {
+            ty.add_to(vm)?;
         }
         vm.qed()?;
-        self.expr.add_to(vm)?;
-        vm.qed()?;
+        let vars: Vec<_> = self.types.iter().map(|_| scope.fresh()).collect();
+        let expr = (self.body)(vars.clone());
+        scope.vars.extend(&vars);
+        let res = expr.add_scoped(vm, scope);
+        scope.vars.truncate(scope.vars.len() - vars.len());
+        res?;
+        for _ in &vars {
+            vm.qed()?;
+        }
         Ok(())
     }
 }
 
-pub struct Concept<'a, T> {
+fn add_args<T: InstructionSet + ?Sized>(
+    vm: &mut T,
+    scope: &mut Scope,
+    args: &[Ptr<'_, T>],
+) -> Result<()> {
+    for arg in args {
+        vm.syn()?;
+        arg.add_scoped(vm, scope)?;
+        vm.app()?;
+    }
+    Ok(())
+}
+
+/// `func` applied to `args` in order
+pub struct Apply<'a, T: ?Sized> {
+    func: Ptr<'a, T>,
+    args: Vec<Ptr<'a, T>>,
+}
+impl<'a, T: InstructionSet + ?Sized> Apply<'a, T> {
+    pub fn new(func: Ptr<'a, T>, args: Vec<Ptr<'a, T>>) -> Self {
+        Self { func, args }
+    }
+}
+impl<'a, T: InstructionSet + ?Sized> Expression<T> for Apply<'a, T> {
+    fn add_scoped(&self, vm: &mut T, scope: &mut Scope) -> Result<()> {
+        self.func.add_scoped(vm, scope)?;
+        add_args(vm, scope, &self.args)
+    }
+}
+
+pub struct Concept<'a, T: ?Sized> {
     name: &'static str,
     args: Vec<Ptr<'a, T>>,
 }
-impl<'a, T: InstructionSet> Concept<'a, T> {
+impl<'a, T: InstructionSet + ?Sized> Concept<'a, T> {
     pub fn new(name: &'static str, args: Vec<Ptr<'a, T>>) -> Self {
         Self { name, args }
     }
 }
-impl<'a, T: InstructionSet> Expression<T> for Concept<'a, T> {
-    fn add_to(&self, vm: &mut T) -> Result<()> {
-        vm.req(&self.name)?;
-        for arg in &self.args {
-            vm.syn()?;
-            arg.add_to(vm)?;
-            vm.app()?;
-        }
-        Ok(())
+impl<'a, T: InstructionSet + ?Sized> Expression<T> for Concept<'a, T> {
+    fn add_scoped(&self, vm: &mut T, scope: &mut Scope) -> Result<()> {
+        vm.req(self.name)?;
+        add_args(vm, scope, &self.args)
     }
 }
 
-pub fn imply<'a, T: InstructionSet + 'a>(a: Ptr<'a, T>, b: Ptr<'a, T>) -> Ptr<'a, T> {
+pub fn imply<'a, T: InstructionSet + ?Sized + 'a>(a: Ptr<'a, T>, b: Ptr<'a, T>) -> Ptr<'a, T> {
     Box::new(Concept::new("sys::imply", vec![a, b]))
 }
 
-pub fn not<'a, T: InstructionSet + 'a>(a: Ptr<'a, T>) -> Ptr<'a, T> {
+pub fn not<'a, T: InstructionSet + ?Sized + 'a>(a: Ptr<'a, T>) -> Ptr<'a, T> {
     Box::new(Concept::new("sys::not", vec![a]))
 }
+
+/// An expression used in several places
+struct Shared<'a, T: ?Sized>(Rc<dyn Expression<T> + 'a>);
+impl<'a, T: InstructionSet + ?Sized + 'a> Shared<'a, T> {
+    fn ptr(&self) -> Ptr<'a, T> {
+        Box::new(Shared(self.0.clone()))
+    }
+}
+impl<'a, T: InstructionSet + ?Sized> Expression<T> for Shared<'a, T> {
+    fn add_scoped(&self, vm: &mut T, scope: &mut Scope) -> Result<()> {
+        self.0.add_scoped(vm, scope)
+    }
+}
+
+/// `!(a -> !b)`
+pub fn and<'a, T: InstructionSet + ?Sized + 'a>(a: Ptr<'a, T>, b: Ptr<'a, T>) -> Ptr<'a, T> {
+    not(imply(a, not(b)))
+}
+
+/// `!a -> b`
+pub fn or<'a, T: InstructionSet + ?Sized + 'a>(a: Ptr<'a, T>, b: Ptr<'a, T>) -> Ptr<'a, T> {
+    imply(not(a), b)
+}
+
+/// `(a -> b) & (b -> a)`
+pub fn iff<'a, T: InstructionSet + ?Sized + 'a>(a: Ptr<'a, T>, b: Ptr<'a, T>) -> Ptr<'a, T> {
+    let (a, b) = (Shared(a.into()), Shared(b.into()));
+    and(imply(a.ptr(), b.ptr()), imply(b.ptr(), a.ptr()))
+}
+
+/// `!∀x. !f(x)`, for which the kernel has no rules
+pub fn exists<'a, T, F>(f: F) -> Ptr<'a, T>
+where
+    T: InstructionSet + ?Sized + 'a,
+    F: Fn(ForallArg) -> Ptr<'a, T> + 'a,
+{
+    not(Box::new(Forall::new(1, move |x| not(f(x[0])))))
+}
+
+/// Modus ponens on the proofs of `p -> q` and `p`, giving `q`
+pub struct Mp<'a, T: ?Sized> {
+    imp: Ptr<'a, T>,
+    p: Ptr<'a, T>,
+}
+impl<'a, T: InstructionSet + ?Sized> Expression<T> for Mp<'a, T> {
+    fn add_scoped(&self, vm: &mut T, scope: &mut Scope) -> Result<()> {
+        self.imp.add_scoped(vm, scope)?;
+        self.p.add_scoped(vm, scope)?;
+        vm.mp()
+    }
+}
+
+pub fn mp<'a, T: InstructionSet + ?Sized + 'a>(imp: Ptr<'a, T>, p: Ptr<'a, T>) -> Ptr<'a, T> {
+    Box::new(Mp { imp, p })
+}
+
+/// A propositional formula over `sys::imply` and `sys::not`, which unlike a
+/// [`Ptr`] can be inspected, compared and cloned
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Prop {
+    Var(ForallArg),
+    /// A symbol without arguments
+    Atom(Rc<str>),
+    Not(Rc<Prop>),
+    Imply(Rc<Prop>, Rc<Prop>),
+}
+impl Prop {
+    pub fn atom(name: &str) -> Self {
+        Prop::Atom(name.into())
+    }
+
+    pub fn not(&self) -> Self {
+        Prop::Not(Rc::new(self.clone()))
+    }
+
+    pub fn imply(&self, q: &Prop) -> Self {
+        Prop::Imply(Rc::new(self.clone()), Rc::new(q.clone()))
+    }
+
+    /// `Some((p, q))` for `p -> q`
+    pub fn split_imply(&self) -> Option<(&Prop, &Prop)> {
+        match self {
+            Prop::Imply(p, q) => Some((p, q)),
+            _ => None,
+        }
+    }
+
+    pub fn ptr<'a, T: InstructionSet + ?Sized + 'a>(&self) -> Ptr<'a, T> {
+        Box::new(self.clone())
+    }
+}
+impl From<ForallArg> for Prop {
+    fn from(x: ForallArg) -> Self {
+        Prop::Var(x)
+    }
+}
+impl<T: InstructionSet + ?Sized> Expression<T> for Prop {
+    fn add_scoped(&self, vm: &mut T, scope: &mut Scope) -> Result<()> {
+        match self {
+            Prop::Var(x) => x.add_scoped(vm, scope),
+            Prop::Atom(name) => vm.req(name),
+            Prop::Not(p) => {
+                vm.req("sys::not")?;
+                add_args(vm, scope, &[p.ptr()])
+            }
+            Prop::Imply(p, q) => {
+                vm.req("sys::imply")?;
+                add_args(vm, scope, &[p.ptr(), q.ptr()])
+            }
+        }
+    }
+}
+/// Variables are written `$n` by creation order
+impl fmt::Display for Prop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Prop::Var(x) => write!(f, "${}", x.0),
+            Prop::Atom(name) => write!(f, "{}", name),
+            Prop::Not(p) => write!(f, "!{}", p),
+            Prop::Imply(p, q) => write!(f, "({} -> {})", p, q),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asm, vm::Verifier};
+
+    /// Records the instructions it is given
+    #[derive(Default)]
+    struct Trace(Vec<String>);
+
+    impl InstructionSet for Trace {
+        fn uni(&mut self) -> Result<()> {
+            self.0.push("uni".into());
+            Ok(())
+        }
+        fn var(&mut self) -> Result<()> {
+            self.0.push("var".into());
+            Ok(())
+        }
+        fn hkt(&mut self) -> Result<()> {
+            self.0.push("hkt".into());
+            Ok(())
+        }
+        fn qed(&mut self) -> Result<()> {
+            self.0.push("qed".into());
+            Ok(())
+        }
+        fn mp(&mut self) -> Result<()> {
+            self.0.push("mp".into());
+            Ok(())
+        }
+        fn app(&mut self) -> Result<()> {
+            self.0.push("app".into());
+            Ok(())
+        }
+        fn req(&mut self, s: &str) -> Result<()> {
+            self.0.push(format!("req {}", s));
+            Ok(())
+        }
+        fn def(&mut self, s: String) -> Result<()> {
+            self.0.push(format!("def {}", s));
+            Ok(())
+        }
+        fn hyp(&mut self, s: String) -> Result<()> {
+            self.0.push(format!("hyp {}", s));
+            Ok(())
+        }
+        fn obj(&mut self, n: usize, s: String) -> Result<()> {
+            self.0.push(format!("obj {} {}", n, s));
+            Ok(())
+        }
+        fn syn(&mut self) -> Result<()> {
+            self.0.push("syn".into());
+            Ok(())
+        }
+        fn sat(&mut self) -> Result<()> {
+            self.0.push("sat".into());
+            Ok(())
+        }
+        fn arg(&mut self, n: NonZeroUsize) -> Result<()> {
+            self.0.push(format!("arg {}", n));
+            Ok(())
+        }
+        fn dup(&mut self) -> Result<()> {
+            self.0.push("dup".into());
+            Ok(())
+        }
+        fn pick(&mut self, n: NonZeroUsize) -> Result<()> {
+            self.0.push(format!("pick {}", n));
+            Ok(())
+        }
+        fn swap(&mut self) -> Result<()> {
+            self.0.push("swap".into());
+            Ok(())
+        }
+        fn drop(&mut self) -> Result<()> {
+            self.0.push("drop".into());
+            Ok(())
+        }
+        fn has(&self, _: &str) -> bool {
+            false
+        }
+        fn depth(&self) -> usize {
+            0
+        }
+    }
+
+    #[test]
+    fn test_closes_binders() {
+        let mut trace = Trace::default();
+        Forall::new(3, |v| imply(v[0].into(), imply(v[1].into(), v[2].into())))
+            .export(&mut trace, "k".into(), false)
+            .unwrap();
+        assert_eq!(
+            trace.0.join(" "),
+            "syn uni var var var qed \
+             req sys::imply syn arg 3 app \
+             syn req sys::imply syn arg 2 app syn arg 1 app app \
+             qed qed qed hyp k"
+        );
+    }
+
+    fn forall<'a, F>(f: F) -> Ptr<'a, Verifier>
+    where
+        F: Fn(ForallArg) -> Ptr<'a, Verifier> + 'a,
+    {
+        Box::new(Forall::new(1, move |x| f(x[0])))
+    }
+
+    #[test]
+    fn test_nested() {
+        let mut vm: Verifier = Verifier::default();
+        // ∀a. (∀b. a -> b) -> a
+        let e = forall(|a| imply(forall(move |b| imply(a.into(), b.into())), a.into()));
+        e.export(&mut vm, "peirce_ish".into(), false).unwrap();
+        assert_eq!(
+            vm.show("peirce_ish").unwrap(),
+            "∀a. sys::imply(∀b. sys::imply(a, b), a)"
+        );
+
+        // variables of every enclosing binder, inside an argument of the session
+        asm::run(&mut vm, "syn uni var qed").unwrap();
+        let e = Forall::new(2, |ab| {
+            let (a, b) = (ab[0], ab[1]);
+            forall(move |c| {
+                imply(
+                    b.into(),
+                    imply(
+                        forall(move |d| imply(a.into(), imply(c.into(), d.into()))),
+                        not(b.into()),
+                    ),
+                )
+            })
+        });
+        e.add_to(&mut vm).unwrap();
+        asm::run(&mut vm, "qed hyp deep").unwrap();
+        assert_eq!(
+            vm.show("deep").unwrap(),
+            "∀a. ∀b. ∀c. ∀d. sys::imply(c, sys::imply(∀e. sys::imply(b, sys::imply(d, e)), sys::not(c)))"
+        );
+    }
+
+    #[test]
+    fn test_unbound() {
+        let mut vm: Verifier = Verifier::default();
+        let leaked = std::cell::Cell::new(None);
+        let e = forall(|x| {
+            leaked.set(Some(x));
+            x.into()
+        });
+        e.export(&mut vm, "id".into(), false).unwrap();
+        // numbered from the scope of the emission
+        let x = leaked.get().unwrap();
+        assert_eq!(Prop::Var(x).to_string(), "$0");
+        let e: Ptr<Verifier> = x.into();
+        assert!(matches!(
+            e.add_to(&mut vm),
+            Err(OperationError::UnboundVariable { var: 0 })
+        ));
+        assert!(vm.show_stack().is_empty());
+    }
+
+    #[test]
+    fn test_higher_order() {
+        use VarType::*;
+        let mut vm: Verifier = Verifier::default();
+        let pred = VarType::func(Symbol, Symbol);
+        // ∀P: @=>@. ∀x. P x -> P x
+        let e = Forall::typed(vec![pred.clone()], |p| {
+            forall(move |x| {
+                let p = p[0];
+                imply(p.apply(vec![x.into()]), p.apply(vec![x.into()]))
+            })
+        });
+        e.export(&mut vm, "refl".into(), false).unwrap();
+        assert_eq!(vm.show("refl").unwrap(), "∀a. ∀b. sys::imply(a(b), a(b))");
+
+        // ∀F: (@=>@)=>@. ∀P: @=>@. ∀x. F P -> P x
+        let hof = VarType::func(pred.clone(), Symbol);
+        assert_eq!(hof.to_string(), "(@=>@)=>@");
+        let e = Forall::typed(vec![hof.clone(), pred.clone(), Symbol], |v| {
+            let (f, p, x) = (v[0], v[1], v[2]);
+            imply(f.apply(vec![p.into()]), p.apply(vec![x.into()]))
+        });
+        e.export(&mut vm, "hof".into(), false).unwrap();
+        assert_eq!(
+            vm.show("hof").unwrap(),
+            "∀a. ∀b. ∀c. sys::imply(a(b), b(c))"
+        );
+
+        // F only takes a predicate
+        let e = Forall::typed(vec![hof, Symbol], |v| v[0].apply(vec![v[1].into()]));
+        assert!(e.export(&mut vm, "bad".into(), false).is_err());
+
+        // nothing to bind
+        let mut vm: Verifier = Verifier::default();
+        let e: Forall<Verifier> = Forall::typed(Vec::new(), |_| Box::new(Prop::atom("p")));
+        assert!(matches!(
+            e.add_to(&mut vm),
+            Err(OperationError::EmptyForall)
+        ));
+        assert!(vm.show_stack().is_empty());
+    }
+}
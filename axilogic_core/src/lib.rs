@@ -1,6 +1,12 @@
-mod ds;
+pub mod asm;
+pub mod deduce;
+pub mod ds;
 pub mod err;
 pub mod isa;
+pub mod kernel;
+pub mod loader;
+pub mod prelude;
 pub mod vm;
-mod util;
-mod kit;
+pub mod taut;
+pub mod util;
+pub mod kit;
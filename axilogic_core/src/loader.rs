@@ -0,0 +1,595 @@
+//! Modules loaded from library roots.
+//!
+//! The symbol `a::b::c` is the export `c` of module `a::b`, which is read from
+//! `a/b.thm` under the first library root that has it. Within a module,
+//!
+//! - names given to `def`, `hyp` and `obj` are qualified by the module path,
+//!   and cannot contain `::`,
+//! - an unqualified `req x` refers to the module's own `x`,
+//! - each leading `super` steps out of the module path, so that `super::d::x`
+//!   in `a::b` is `a::d::x`,
+//! - any other name is absolute.
+//!
+//! Outside of any module, `def`, `hyp` and `obj` cannot name a symbol of a
+//! module that has a file or is built in, so a program cannot stand in for
+//! the module it would load.
+//!
+//! Before a program runs, the modules it requires are loaded into the same
+//! instruction set, each at most once. A module is loaded on an empty stack
+//! and must leave it empty, as a finished session does. Each segment of a
+//! module name is a plain file name, so a module cannot be read from outside
+//! the library roots.
+//!
+//! A module that fails to load leaves no trace: the instruction set is rewound
+//! to where it started, along with the modules it loaded on the way, so that
+//! loading it again reports the same error.
+//!
+//! The [`prelude`] is built in, and is used when no root has a `prelude`
+//! module of its own.
+
+use std::{
+    collections::HashSet,
+    error::Error as StdError,
+    fmt, fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    asm::{self, Instruction, Pos},
+    err::OperationError,
+    isa::{InstructionSet, Recover},
+    prelude,
+};
+
+pub const EXTENSION: &str = "thm";
+const SEPARATOR: &str = "::";
+const PARENT: &str = "super::";
+
+#[derive(Debug)]
+pub enum Error {
+    NotFound(String),
+    /// A segment of the module name is not a plain file name
+    InvalidName(String),
+    /// The stack has entries when a module is to be loaded
+    Busy {
+        name: String,
+        depth: usize,
+    },
+    /// The module leaves entries on the stack
+    Unfinished {
+        name: String,
+        depth: usize,
+    },
+    Io {
+        path: PathBuf,
+        err: io::Error,
+    },
+    /// A `def`, `hyp` or `obj` in `module`, or outside of any module if
+    /// `None`, names a symbol of another module
+    ForeignName {
+        module: Option<String>,
+        name: String,
+    },
+    /// Each module requires the next, and the last one is the first
+    Cycle(Vec<String>),
+    /// Failure inside a module file
+    Module {
+        name: String,
+        path: PathBuf,
+        err: Box<asm::Error>,
+    },
+    /// Failure while installing a built-in module
+    Builtin {
+        name: String,
+        err: OperationError,
+    },
+    /// Failure of the program passed to [`Loader::run`]
+    Asm(Box<asm::Error>),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NotFound(name) => write!(f, "Module not found: {}", name),
+            Error::InvalidName(name) => write!(f, "Invalid module name: {}", name),
+            Error::Busy { name, depth } => write!(
+                f,
+                "Cannot load module {} with {} entries on the stack",
+                name, depth
+            ),
+            Error::Unfinished { name, depth } => {
+                write!(f, "Module {} leaves {} entries on the stack", name, depth)
+            }
+            Error::Io { path, err } => write!(f, "{}: {}", path.display(), err),
+            Error::ForeignName { module, name } => {
+                write!(f, "Name {} belongs to another module", name)?;
+                match module {
+                    Some(m) => write!(f, " than {}", m),
+                    None => Ok(()),
+                }
+            }
+            Error::Cycle(path) => write!(f, "Import cycle: {}", path.join(" -> ")),
+            Error::Module { name, path, err } => {
+                write!(f, "In module {} ({}): {}", name, path.display(), err)
+            }
+            Error::Builtin { name, err } => write!(f, "In built-in module {}: {}", name, err),
+            Error::Asm(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl StdError for Error {}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Keeps track of the modules loaded into one instruction set, so the same
+/// loader must be used with the same instruction set throughout.
+#[derive(Clone)]
+pub struct Loader {
+    roots: Vec<PathBuf>,
+    loaded: HashSet<String>,
+    loading: Vec<String>,
+}
+
+impl Loader {
+    pub fn new(roots: Vec<PathBuf>) -> Self {
+        Self {
+            roots,
+            loaded: HashSet::new(),
+            loading: Vec::new(),
+        }
+    }
+
+    /// The file of `module` under the first root that has it
+    pub fn find(&self, module: &str) -> Option<PathBuf> {
+        let rel = relative_path(module)?;
+        self.roots
+            .iter()
+            .map(|x| x.join(&rel))
+            .find(|x| x.is_file())
+    }
+
+    pub fn is_loaded(&self, module: &str) -> bool {
+        self.loaded.contains(module)
+    }
+
+    /// Treat `module` as loaded, once its symbols were added by other means
+    /// such as an image
+    pub fn mark_loaded(&mut self, module: &str) {
+        self.loaded.insert(module.into());
+    }
+
+    /// The modules that `prog` may load, run in `module` or outside of any
+    /// module if `None`: those of its `req`s that have a file or are built in.
+    /// A module whose symbols are all present already is not loaded by
+    /// [`load`](Self::load), so this is an upper bound.
+    pub fn requires(&self, module: Option<&str>, prog: &[(Pos, Instruction)]) -> Vec<String> {
+        let mut ans: Vec<String> = Vec::new();
+        for (_, ins) in prog {
+            let Instruction::Req(s) = ins else {
+                continue;
+            };
+            let s = resolve(module, s.clone());
+            if let Some((m, _)) = s.rsplit_once(SEPARATOR) {
+                if self.has_module(module, m) && !ans.iter().any(|x| x == m) {
+                    ans.push(m.into());
+                }
+            }
+        }
+        ans
+    }
+
+    /// The modules that `module` may load, read from its file
+    pub fn dependencies(&self, module: &str) -> Result<Vec<String>> {
+        if relative_path(module).is_none() {
+            return Err(Error::InvalidName(module.into()));
+        }
+        match self.find(module) {
+            Some(path) => Ok(self.requires(Some(module), &parse_module(module, &path)?)),
+            None if module == prelude::MODULE => Ok(Vec::new()),
+            None => Err(Error::NotFound(module.into())),
+        }
+    }
+
+    /// Verify `module` and its dependencies, unless it is already loaded.
+    ///
+    /// On failure, `vm` and the loader are left as they were.
+    pub fn load<T: Recover + ?Sized>(&mut self, vm: &mut T, module: &str) -> Result<()> {
+        if self.loaded.contains(module) {
+            return Ok(());
+        }
+        let start = (vm.snapshot(), self.loaded.clone());
+        let res = self.load_new(vm, module);
+        if res.is_err() {
+            vm.rewind(&start.0)
+                .expect("a snapshot of the same instruction set");
+            self.loaded = start.1;
+        }
+        res
+    }
+
+    fn load_new<T: Recover + ?Sized>(&mut self, vm: &mut T, module: &str) -> Result<()> {
+        if let Some(i) = self.loading.iter().position(|x| x == module) {
+            let mut path = self.loading[i..].to_vec();
+            path.push(module.into());
+            return Err(Error::Cycle(path));
+        }
+        if relative_path(module).is_none() {
+            return Err(Error::InvalidName(module.into()));
+        }
+        if vm.depth() > 0 {
+            return Err(Error::Busy {
+                name: module.into(),
+                depth: vm.depth(),
+            });
+        }
+        let path = match self.find(module) {
+            Some(path) => path,
+            None if module == prelude::MODULE => {
+                prelude::install(vm).map_err(|err| Error::Builtin {
+                    name: module.into(),
+                    err,
+                })?;
+                return self.finish(vm, module);
+            }
+            None => return Err(Error::NotFound(module.into())),
+        };
+        let prog = parse_module(module, &path)?;
+        let wrap = |err| Error::Module {
+            name: module.into(),
+            path: path.clone(),
+            err: Box::new(err),
+        };
+        self.loading.push(module.into());
+        let res = self
+            .link_in(vm, Some(module), prog)
+            .and_then(|prog| asm::execute(vm, &prog).map_err(wrap));
+        self.loading.pop();
+        res?;
+        self.finish(vm, module)
+    }
+
+    /// Mark `module` as loaded if it left the stack empty
+    fn finish<T: InstructionSet + ?Sized>(&mut self, vm: &T, module: &str) -> Result<()> {
+        if vm.depth() > 0 {
+            return Err(Error::Unfinished {
+                name: module.into(),
+                depth: vm.depth(),
+            });
+        }
+        self.loaded.insert(module.into());
+        Ok(())
+    }
+
+    /// Load the modules required by a program outside of any module.
+    ///
+    /// The program is returned unchanged, ready to be executed.
+    pub fn link<T: Recover + ?Sized>(
+        &mut self,
+        vm: &mut T,
+        prog: Vec<(Pos, Instruction)>,
+    ) -> Result<Vec<(Pos, Instruction)>> {
+        self.link_in(vm, None, prog)
+    }
+
+    /// Parse a program, load its dependencies and execute it.
+    pub fn run<T: Recover + ?Sized>(&mut self, vm: &mut T, src: &str) -> Result<()> {
+        let prog = asm::parse(src).map_err(|e| Error::Asm(Box::new(e.into())))?;
+        let prog = self.link(vm, prog)?;
+        asm::execute(vm, &prog).map_err(|e| Error::Asm(Box::new(e)))
+    }
+
+    fn link_in<T: Recover + ?Sized>(
+        &mut self,
+        vm: &mut T,
+        module: Option<&str>,
+        prog: Vec<(Pos, Instruction)>,
+    ) -> Result<Vec<(Pos, Instruction)>> {
+        use Instruction::*;
+        prog.into_iter()
+            .map(|(pos, ins)| {
+                let ins = match ins {
+                    Req(s) => {
+                        let s = resolve(module, s);
+                        self.require(vm, module, &s)?;
+                        Req(s)
+                    }
+                    Def(s) => Def(self.qualify(module, s)?),
+                    Hyp(s) => Hyp(self.qualify(module, s)?),
+                    Obj(n, s) => Obj(n, self.qualify(module, s)?),
+                    ins => ins,
+                };
+                Ok((pos, ins))
+            })
+            .collect()
+    }
+
+    /// The name of a symbol that `module` adds, if it is not one of another
+    /// module's
+    fn qualify(&self, module: Option<&str>, name: String) -> Result<String> {
+        let foreign = match module {
+            Some(_) => name.contains(SEPARATOR),
+            None => name
+                .match_indices(SEPARATOR)
+                .any(|(i, _)| self.has_module(None, &name[..i])),
+        };
+        if foreign {
+            return Err(Error::ForeignName {
+                module: module.map(Into::into),
+                name,
+            });
+        }
+        Ok(match module {
+            Some(m) => format!("{}{}{}", m, SEPARATOR, name),
+            None => name,
+        })
+    }
+
+    /// Load the module of `sym` if it is missing and has a file
+    fn require<T: Recover + ?Sized>(
+        &mut self,
+        vm: &mut T,
+        current: Option<&str>,
+        sym: &str,
+    ) -> Result<()> {
+        if vm.has(sym) {
+            return Ok(());
+        }
+        let Some((module, _)) = sym.rsplit_once(SEPARATOR) else {
+            return Ok(());
+        };
+        if !self.has_module(current, module) {
+            // left for `req` to report
+            return Ok(());
+        }
+        self.load(vm, module)
+    }
+
+    /// Whether `module`, required from `current`, is another module with a
+    /// file or built in
+    fn has_module(&self, current: Option<&str>, module: &str) -> bool {
+        current != Some(module) && (self.find(module).is_some() || module == prelude::MODULE)
+    }
+}
+
+/// The program in the file of `module`
+fn parse_module(module: &str, path: &Path) -> Result<Vec<(Pos, Instruction)>> {
+    let src = fs::read_to_string(path).map_err(|err| Error::Io {
+        path: path.into(),
+        err,
+    })?;
+    asm::parse(&src).map_err(|e| Error::Module {
+        name: module.into(),
+        path: path.into(),
+        err: Box::new(e.into()),
+    })
+}
+
+/// The file of `module` relative to a library root, if every segment of the
+/// name is a plain file name
+fn relative_path(module: &str) -> Option<PathBuf> {
+    let mut rel = PathBuf::new();
+    for seg in module.split(SEPARATOR) {
+        match Path::new(seg).components().collect::<Vec<_>>()[..] {
+            [Component::Normal(x)] if x == seg => rel.push(x),
+            _ => return None,
+        }
+    }
+    rel.set_extension(EXTENSION);
+    Some(rel)
+}
+
+/// The absolute name of `name` required from `module`
+fn resolve(module: Option<&str>, name: String) -> String {
+    let Some(module) = module else {
+        return name;
+    };
+    if !name.contains(SEPARATOR) {
+        return format!("{}{}{}", module, SEPARATOR, name);
+    }
+    let mut base: Vec<_> = module.split(SEPARATOR).collect();
+    let mut rest = name.as_str();
+    if !rest.starts_with(PARENT) {
+        return name;
+    }
+    while let Some(x) = rest.strip_prefix(PARENT) {
+        if base.pop().is_none() {
+            return name;
+        }
+        rest = x;
+    }
+    base.push(rest);
+    base.join(SEPARATOR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::Verifier;
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(resolve(None, "x".into()), "x");
+        assert_eq!(resolve(Some("a::b"), "x".into()), "a::b::x");
+        assert_eq!(resolve(Some("a::b"), "sys::l1".into()), "sys::l1");
+        assert_eq!(resolve(Some("a::b"), "super::d::x".into()), "a::d::x");
+        assert_eq!(resolve(Some("a::b"), "super::super::d::x".into()), "d::x");
+        assert_eq!(
+            resolve(Some("a"), "super::super::x".into()),
+            "super::super::x"
+        );
+    }
+
+    #[test]
+    fn test_load() {
+        let root = std::env::temp_dir().join(format!("axilogic-loader-{}", std::process::id()));
+        let files = [
+            (
+                "logic/basic.thm",
+                "syn uni var qed req sys::imply syn arg 1 app syn arg 1 app qed hyp self_imply",
+            ),
+            (
+                "logic/more.thm",
+                "syn req super::basic::self_imply hyp again
+                 syn req again hyp again2",
+            ),
+            ("cyc/a.thm", "syn req cyc::b::y hyp x"),
+            ("cyc/b.thm", "syn req cyc::c::z hyp y"),
+            ("cyc/c.thm", "syn req cyc::a::x hyp z"),
+            ("open/a.thm", "syn uni var qed arg 1 qed hyp x"),
+            ("open/b.thm", "syn req sys::l1"),
+            (
+                "ns/a.thm",
+                "syn uni var qed req sys::imply syn arg 1 app syn arg 1 app qed hyp b::x",
+            ),
+            ("ns/a/b.thm", "syn req sys::l1 hyp x"),
+            (
+                "half/a.thm",
+                "syn req logic::basic::self_imply hyp x syn req nope hyp y",
+            ),
+        ];
+        for (path, src) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, src).unwrap();
+        }
+
+        let mut vm: Verifier = Verifier::default();
+        let mut loader = Loader::new(vec![root.join("missing"), root.clone()]);
+        loader
+            .run(
+                &mut vm,
+                "syn req logic::more::again2 hyp x syn req logic::basic::self_imply hyp x2",
+            )
+            .unwrap();
+        assert!(loader.is_loaded("logic::basic") && loader.is_loaded("logic::more"));
+        assert!(vm.has("logic::more::again2") && vm.has("x"));
+        // loaded once
+        loader.load(&mut vm, "logic::basic").unwrap();
+        match loader.run(&mut vm, "syn req logic::basic::nope hyp y") {
+            Err(Error::Asm(e)) => assert!(matches!(*e, asm::Error::Operation { .. })),
+            _ => panic!(),
+        }
+        // the failed program left `syn` behind
+        match loader.load(&mut vm, "cyc::b") {
+            Err(Error::Busy { name, depth }) => assert_eq!((name.as_str(), depth), ("cyc::b", 1)),
+            _ => panic!(),
+        }
+
+        let mut vm: Verifier = Verifier::default();
+        let mut loader = Loader::new(vec![root.clone()]);
+        match loader.load(&mut vm, "cyc::b") {
+            Err(Error::Cycle(path)) => assert_eq!(path, ["cyc::b", "cyc::c", "cyc::a", "cyc::b"]),
+            _ => panic!(),
+        }
+        assert!(matches!(
+            loader.load(&mut vm, "nope"),
+            Err(Error::NotFound(_))
+        ));
+        // modules must end with an empty stack
+        assert!(loader.load(&mut vm, "open::a").is_ok());
+        match loader.load(&mut vm, "open::b") {
+            Err(Error::Unfinished { name, depth }) => {
+                assert_eq!((name.as_str(), depth), ("open::b", 2))
+            }
+            _ => panic!(),
+        }
+        assert!(!loader.is_loaded("open::b") && vm.depth() == 0);
+
+        // a module that fails partway is undone with its dependencies
+        let mut vm: Verifier = Verifier::default();
+        let mut loader = Loader::new(vec![root.clone()]);
+        for _ in 0..2 {
+            match loader.load(&mut vm, "half::a") {
+                Err(Error::Module { name, err, .. }) => {
+                    assert_eq!(name, "half::a");
+                    assert!(matches!(*err, asm::Error::Operation { .. }))
+                }
+                _ => panic!(),
+            }
+            assert!(!vm.has("half::a::x") && !vm.has("logic::basic::self_imply"));
+            assert!(!loader.is_loaded("logic::basic") && vm.depth() == 0);
+        }
+        // so it cannot satisfy a later `req`
+        assert!(matches!(
+            loader.run(&mut vm, "syn req half::a::x hyp y"),
+            Err(Error::Module { .. })
+        ));
+
+        // names cannot leave the library roots
+        let mut vm: Verifier = Verifier::default();
+        let mut loader = Loader::new(vec![root.join("cyc")]);
+        for name in ["..::logic::basic", ".::a", "::a", "a::"] {
+            assert!(loader.find(name).is_none(), "{}", name);
+            assert!(
+                matches!(loader.load(&mut vm, name), Err(Error::InvalidName(_))),
+                "{}",
+                name
+            );
+        }
+        let abs = format!("{}{}logic::basic", root.display(), SEPARATOR);
+        assert!(matches!(
+            loader.load(&mut vm, &abs),
+            Err(Error::InvalidName(_))
+        ));
+        // the built-in prelude
+        let mut vm: Verifier = Verifier::default();
+        let mut loader = Loader::new(vec![root.clone()]);
+        loader
+            .run(&mut vm, "syn req prelude::dne hyp my_dne")
+            .unwrap();
+        assert!(loader.is_loaded("prelude") && vm.has("my_dne"));
+
+        // a module cannot add the symbols of another one
+        let mut vm: Verifier = Verifier::default();
+        let mut loader = Loader::new(vec![root.clone()]);
+        match loader.load(&mut vm, "ns::a") {
+            Err(Error::ForeignName { module, name }) => {
+                assert_eq!((module.as_deref(), name.as_str()), (Some("ns::a"), "b::x"))
+            }
+            _ => panic!(),
+        }
+        assert!(!vm.has("ns::a::b::x"));
+        loader.run(&mut vm, "syn req ns::a::b::x hyp y").unwrap();
+        assert!(loader.is_loaded("ns::a::b"));
+        // nor can a program outside of any module, ahead of loading it
+        assert!(matches!(
+            loader.run(&mut vm, "syn req sys::l1 hyp prelude::and_intro"),
+            Err(Error::ForeignName { module: None, .. })
+        ));
+        loader
+            .run(&mut vm, "syn req prelude::and_intro hyp my_and_intro")
+            .unwrap();
+        assert!(loader.is_loaded("prelude"));
+        // names of no module are free
+        loader.run(&mut vm, "obj 0 my::p").unwrap();
+
+        // the modules a program may load, read without loading them
+        let loader = Loader::new(vec![root.clone()]);
+        assert_eq!(
+            loader.dependencies("logic::more").unwrap(),
+            ["logic::basic"]
+        );
+        assert_eq!(loader.dependencies("cyc::c").unwrap(), ["cyc::a"]);
+        assert!(loader.dependencies("prelude").unwrap().is_empty());
+        assert!(matches!(
+            loader.dependencies("nope"),
+            Err(Error::NotFound(_))
+        ));
+        let prog = asm::parse("req logic::more::again req sys::l1 req prelude::dne req nope::x");
+        assert_eq!(
+            loader.requires(None, &prog.unwrap()),
+            ["logic::more", "prelude"]
+        );
+        // a module marked as loaded is not read again
+        let mut vm: Verifier = Verifier::default();
+        let mut loader = Loader::new(vec![root.clone()]);
+        loader.mark_loaded("logic::basic");
+        assert!(matches!(
+            loader.load(&mut vm, "logic::more"),
+            Err(Error::Module { .. })
+        ));
+        assert!(!vm.has("logic::basic::self_imply"));
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,382 @@
+//! Conjunction, disjunction and the biconditional over `sys::imply` and
+//! `sys::not`, with their rules proven from `sys::l1`, `sys::l2` and
+//! `sys::l3`.
+//!
+//! The connectives are abbreviations built by [`kit::and`], [`kit::or`] and
+//! [`kit::iff`]:
+//!
+//! - `a & b` is `!(a -> !b)`,
+//! - `a | b` is `!a -> b`,
+//! - `a <-> b` is `(a -> b) & (b -> a)`.
+//!
+//! [`install`] proves every lemma below in synthetic mode, by `mp` from the
+//! axioms and the earlier lemmas, and exports it as a `hyp` named
+//! `prelude::<name>`, universally quantified over its variables in order.
+//! The [`Loader`](crate::loader::Loader) installs them when a program
+//! requires a `prelude::` symbol and no library root has a `prelude` module.
+
+use crate::{
+    err::Result,
+    isa::InstructionSet,
+    kit::{mp, Concept, Expression, Forall, Prop, Ptr},
+};
+
+/// The module the lemmas are exported in
+pub const MODULE: &str = "prelude";
+
+fn not(a: &Prop) -> Prop {
+    a.not()
+}
+
+fn imp(a: &Prop, b: &Prop) -> Prop {
+    a.imply(b)
+}
+
+/// The theorem `name` instantiated with `args`
+fn by<'a, T: InstructionSet + ?Sized + 'a>(name: &'static str, args: &[&Prop]) -> Ptr<'a, T> {
+    Box::new(Concept::new(name, args.iter().map(|x| x.ptr()).collect()))
+}
+
+/// `p -> r` from `p -> q` and `q -> r`, through `l1` and `l2`
+fn compose<'a, T: InstructionSet + ?Sized + 'a>(
+    [p, q, r]: [&Prop; 3],
+    pq: Ptr<'a, T>,
+    qr: Ptr<'a, T>,
+) -> Ptr<'a, T> {
+    let pqr = mp(by("sys::l1", &[&imp(q, r), p]), qr);
+    mp(mp(by("sys::l2", &[p, q, r]), pqr), pq)
+}
+
+/// `p -> q` from `p -> (p -> q)`
+fn contract<'a, T: InstructionSet + ?Sized + 'a>(
+    p: &Prop,
+    q: &Prop,
+    ppq: Ptr<'a, T>,
+) -> Ptr<'a, T> {
+    mp(mp(by("sys::l2", &[p, p, q]), ppq), by("prelude::id", &[p]))
+}
+
+type Proof<'a, T> = fn(&[Prop]) -> Ptr<'a, T>;
+
+/// `(name, number of variables, proof)` in dependency order
+fn lemmas<'a, T: InstructionSet + ?Sized + 'a>() -> Vec<(&'static str, usize, Proof<'a, T>)> {
+    vec![
+        // a -> a
+        ("prelude::id", 1, |v| {
+            let a = &v[0];
+            let aa = imp(a, a);
+            mp(
+                mp(by("sys::l2", &[a, &aa, a]), by("sys::l1", &[a, &aa])),
+                by("sys::l1", &[a, a]),
+            )
+        }),
+        // (b -> c) -> (a -> b) -> a -> c
+        ("prelude::syll", 3, |v| {
+            let [a, b, c] = [&v[0], &v[1], &v[2]];
+            compose(
+                [
+                    &imp(b, c),
+                    &imp(a, &imp(b, c)),
+                    &imp(&imp(a, b), &imp(a, c)),
+                ],
+                by("sys::l1", &[&imp(b, c), a]),
+                by("sys::l2", &[a, b, c]),
+            )
+        }),
+        // (a -> b -> c) -> b -> a -> c
+        ("prelude::perm", 3, |v| {
+            let [a, b, c] = [&v[0], &v[1], &v[2]];
+            let p = imp(a, &imp(b, c));
+            let q = imp(b, &imp(a, b));
+            let r = imp(b, &imp(a, c));
+            let (ab, ac) = (imp(a, b), imp(a, c));
+            let pqr = compose(
+                [&p, &imp(&ab, &ac), &imp(&q, &r)],
+                by("sys::l2", &[a, b, c]),
+                by("prelude::syll", &[b, &ab, &ac]),
+            );
+            let pq = mp(by("sys::l1", &[&q, &p]), by("sys::l1", &[b, a]));
+            mp(mp(by("sys::l2", &[&p, &q, &r]), pqr), pq)
+        }),
+        // (a -> b) -> (b -> c) -> a -> c
+        ("prelude::syll2", 3, |v| {
+            let [a, b, c] = [&v[0], &v[1], &v[2]];
+            mp(
+                by("prelude::perm", &[&imp(b, c), &imp(a, b), &imp(a, c)]),
+                by("prelude::syll", &[a, b, c]),
+            )
+        }),
+        // a -> (a -> b) -> b
+        ("prelude::mpl", 2, |v| {
+            let [a, b] = [&v[0], &v[1]];
+            mp(
+                by("prelude::perm", &[&imp(a, b), a, b]),
+                by("prelude::id", &[&imp(a, b)]),
+            )
+        }),
+        // !a -> a -> b
+        ("prelude::efq", 2, |v| {
+            let [a, b] = [&v[0], &v[1]];
+            compose(
+                [&not(a), &imp(&not(b), &not(a)), &imp(a, b)],
+                by("sys::l1", &[&not(a), &not(b)]),
+                by("sys::l3", &[b, a]),
+            )
+        }),
+        // !!a -> a
+        ("prelude::dne", 1, |v| {
+            let a = &v[0];
+            let nna = not(&not(a));
+            let h = compose(
+                [&nna, &imp(&not(a), &not(&nna)), &imp(&nna, a)],
+                by("prelude::efq", &[&not(a), &not(&nna)]),
+                by("sys::l3", &[a, &nna]),
+            );
+            contract(&nna, a, h)
+        }),
+        // a -> !!a
+        ("prelude::dni", 1, |v| {
+            let a = &v[0];
+            mp(
+                by("sys::l3", &[&not(&not(a)), a]),
+                by("prelude::dne", &[&not(a)]),
+            )
+        }),
+        // (!a -> b) -> !b -> a
+        ("prelude::con1", 2, |v| {
+            let [a, b] = [&v[0], &v[1]];
+            let nnb = not(&not(b));
+            compose(
+                [&imp(&not(a), b), &imp(&not(a), &nnb), &imp(&not(b), a)],
+                mp(
+                    by("prelude::syll", &[&not(a), b, &nnb]),
+                    by("prelude::dni", &[b]),
+                ),
+                by("sys::l3", &[a, &not(b)]),
+            )
+        }),
+        // (a -> !b) -> b -> !a
+        ("prelude::con2", 2, |v| {
+            let [a, b] = [&v[0], &v[1]];
+            let nna = not(&not(a));
+            compose(
+                [&imp(a, &not(b)), &imp(&nna, &not(b)), &imp(b, &not(a))],
+                mp(
+                    by("prelude::syll2", &[&nna, a, &not(b)]),
+                    by("prelude::dne", &[a]),
+                ),
+                by("sys::l3", &[&not(a), b]),
+            )
+        }),
+        // (!a -> a) -> a
+        ("prelude::clavius", 1, |v| {
+            let a = &v[0];
+            let y = imp(&not(a), a);
+            let z = not(&y);
+            let s = mp(
+                by("sys::l2", &[&not(a), a, &z]),
+                by("prelude::efq", &[a, &z]),
+            );
+            let u = compose(
+                [&y, &imp(&not(a), &z), &imp(&y, a)],
+                s,
+                by("sys::l3", &[a, &y]),
+            );
+            contract(&y, a, u)
+        }),
+        // (a -> b) -> (!a -> b) -> b
+        ("prelude::cases", 2, |v| {
+            let [a, b] = [&v[0], &v[1]];
+            let (nb_a, nb_b) = (imp(&not(b), a), imp(&not(b), b));
+            let k1 = compose(
+                [&imp(a, b), &imp(&nb_a, &nb_b), &imp(&nb_a, b)],
+                by("prelude::syll", &[&not(b), a, b]),
+                mp(
+                    by("prelude::syll", &[&nb_a, &nb_b, b]),
+                    by("prelude::clavius", &[b]),
+                ),
+            );
+            let k2 = mp(
+                by("prelude::syll2", &[&imp(&not(a), b), &nb_a, b]),
+                by("prelude::con1", &[a, b]),
+            );
+            compose(
+                [&imp(a, b), &imp(&nb_a, b), &imp(&imp(&not(a), b), b)],
+                k1,
+                k2,
+            )
+        }),
+        // a -> b -> a & b
+        ("prelude::and_intro", 2, |v| {
+            let [a, b] = [&v[0], &v[1]];
+            let anb = imp(a, &not(b));
+            compose(
+                [a, &imp(&anb, &not(b)), &imp(b, &not(&anb))],
+                by("prelude::mpl", &[a, &not(b)]),
+                by("prelude::con2", &[&anb, b]),
+            )
+        }),
+        // a & b -> a
+        ("prelude::and_elim1", 2, |v| {
+            let [a, b] = [&v[0], &v[1]];
+            mp(
+                by("prelude::con1", &[a, &imp(a, &not(b))]),
+                by("prelude::efq", &[a, &not(b)]),
+            )
+        }),
+        // a & b -> b
+        ("prelude::and_elim2", 2, |v| {
+            let [a, b] = [&v[0], &v[1]];
+            mp(
+                by("prelude::con1", &[b, &imp(a, &not(b))]),
+                by("sys::l1", &[&not(b), a]),
+            )
+        }),
+        // a -> a | b
+        ("prelude::or_intro1", 2, |v| {
+            let [a, b] = [&v[0], &v[1]];
+            mp(
+                by("prelude::perm", &[&not(a), a, b]),
+                by("prelude::efq", &[a, b]),
+            )
+        }),
+        // b -> a | b
+        ("prelude::or_intro2", 2, |v| {
+            let [a, b] = [&v[0], &v[1]];
+            by("sys::l1", &[b, &not(a)])
+        }),
+        // (a -> c) -> (b -> c) -> a | b -> c
+        ("prelude::or_elim", 3, |v| {
+            let [a, b, c] = [&v[0], &v[1], &v[2]];
+            let (na_b, na_c) = (imp(&not(a), b), imp(&not(a), c));
+            let (bc, cased) = (imp(b, c), imp(&na_c, c));
+            let k3 = compose(
+                [&bc, &imp(&na_b, &na_c), &imp(&cased, &imp(&na_b, c))],
+                by("prelude::syll", &[&not(a), b, c]),
+                by("prelude::syll2", &[&na_b, &na_c, c]),
+            );
+            let k4 = mp(by("prelude::perm", &[&bc, &cased, &imp(&na_b, c)]), k3);
+            compose(
+                [&imp(a, c), &cased, &imp(&bc, &imp(&na_b, c))],
+                by("prelude::cases", &[a, c]),
+                k4,
+            )
+        }),
+        // (a -> b) -> (b -> a) -> (a <-> b)
+        ("prelude::iff_intro", 2, |v| {
+            let [a, b] = [&v[0], &v[1]];
+            by("prelude::and_intro", &[&imp(a, b), &imp(b, a)])
+        }),
+        // (a <-> b) -> a -> b
+        ("prelude::iff_elim1", 2, |v| {
+            let [a, b] = [&v[0], &v[1]];
+            by("prelude::and_elim1", &[&imp(a, b), &imp(b, a)])
+        }),
+        // (a <-> b) -> b -> a
+        ("prelude::iff_elim2", 2, |v| {
+            let [a, b] = [&v[0], &v[1]];
+            by("prelude::and_elim2", &[&imp(a, b), &imp(b, a)])
+        }),
+    ]
+}
+
+/// Names of the lemmas, in the order they are installed
+pub fn names() -> Vec<&'static str> {
+    lemmas::<dyn InstructionSet>()
+        .into_iter()
+        .map(|(name, ..)| name)
+        .collect()
+}
+
+/// Prove and export every lemma, in synthetic mode and with the objects and axioms
+/// of the [`Classical`](crate::kernel::Classical) kernel
+pub fn install<T: InstructionSet + ?Sized>(vm: &mut T) -> Result<()> {
+    for (name, n, proof) in lemmas::<T>() {
+        Forall::new(n, |v| {
+            proof(&v.into_iter().map(Prop::Var).collect::<Vec<_>>())
+        })
+        .export(vm, name.into(), false)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        asm,
+        kernel::Intuitionistic,
+        kit::{and, iff, imply, or},
+        vm::{Assumption, SymbolKind, Verifier},
+    };
+
+    #[test]
+    fn test_install() {
+        let mut vm: Verifier = Verifier::default();
+        install(&mut vm).unwrap();
+        for name in names() {
+            assert_eq!(vm.kind(name), Some(SymbolKind::Hypothesis), "{}", name);
+            // nothing but the kernel and the prelude itself
+            let found = vm.assumptions(name).unwrap();
+            assert!(
+                found.iter().all(|x| match x {
+                    Assumption::Axiom(_) | Assumption::Object(_) => true,
+                    Assumption::Hypothesis(h) => h.starts_with("prelude::"),
+                    Assumption::Sat(_) => false,
+                }),
+                "{}: {:?}",
+                name,
+                found
+            );
+        }
+        assert_eq!(
+            vm.show("prelude::or_elim").unwrap(),
+            "∀a. ∀b. ∀c. sys::imply(sys::imply(a, c), sys::imply(sys::imply(b, c), \
+             sys::imply(sys::imply(sys::not(a), b), c)))"
+        );
+
+        // the statements are the kit connectives
+        let statements: [(&str, Ptr<Verifier>); 3] = [
+            (
+                "and_intro",
+                Box::new(Forall::new(2, |v| {
+                    imply(
+                        v[0].into(),
+                        imply(v[1].into(), and(v[0].into(), v[1].into())),
+                    )
+                })),
+            ),
+            (
+                "or_intro1",
+                Box::new(Forall::new(2, |v| {
+                    imply(v[0].into(), or(v[0].into(), v[1].into()))
+                })),
+            ),
+            (
+                "iff_elim2",
+                Box::new(Forall::new(2, |v| {
+                    imply(
+                        iff(v[0].into(), v[1].into()),
+                        imply(v[1].into(), v[0].into()),
+                    )
+                })),
+            ),
+        ];
+        for (name, e) in statements {
+            e.export(&mut vm, format!("expected_{}", name), false)
+                .unwrap();
+            let expected = vm.show(&format!("expected_{}", name)).unwrap();
+            assert_eq!(vm.show(&format!("prelude::{}", name)).unwrap(), expected);
+        }
+
+        // usable in synthetic mode like any hypothesis
+        asm::run(
+            &mut vm,
+            "obj 0 p obj 0 q syn req prelude::and_elim2 syn req p app syn req q app hyp second",
+        )
+        .unwrap();
+
+        let mut vm: Verifier = Verifier::with_kernel(Default::default(), &Intuitionistic).unwrap();
+        assert!(install(&mut vm).is_err());
+    }
+}
@@ -0,0 +1,315 @@
+//! Proofs of propositional tautologies, following Kalmár's completeness
+//! proof.
+//!
+//! Under a valuation of the atoms of `p`, write `q'` for `q` if `q` is true
+//! and `!q` otherwise. From the assumptions `a'` for every atom `a`, each
+//! subformula `q` proves `q'` by a fixed lemma for its connective. When `p` is
+//! a tautology, `p' = p` under every valuation, and the assumptions on the
+//! atoms are discharged one at a time by case analysis on `a` and `!a`.
+//!
+//! The lemmas are proven inline from `sys::l1`, `sys::l2` and `sys::l3`, so
+//! the result needs nothing else; it takes `2^n` cases for `n` atoms, each a
+//! copy of the proof of `p'`, so formulas with more than [`MAX_ATOMS`] atoms
+//! are refused.
+//!
+//! Like any [`Deduction`], the proof is exported in synthetic mode, as a `hyp`
+//! that depends on nothing but the axioms, the objects and itself.
+
+use std::{collections::HashMap, error::Error as StdError, fmt};
+
+use crate::{
+    deduce::{Deduction, Proof},
+    kit::Prop,
+};
+
+/// The atoms of `p`, variables and symbols, in order of first appearance
+pub fn atoms(p: &Prop) -> Vec<Prop> {
+    fn walk(p: &Prop, out: &mut Vec<Prop>) {
+        match p {
+            Prop::Var(_) | Prop::Atom(_) => {
+                if !out.contains(p) {
+                    out.push(p.clone());
+                }
+            }
+            Prop::Not(q) => walk(q, out),
+            Prop::Imply(q, r) => {
+                walk(q, out);
+                walk(r, out);
+            }
+        }
+    }
+    let mut out = Vec::new();
+    walk(p, &mut out);
+    out
+}
+
+/// The truth value of `p`, with `val` defined on all its atoms
+pub fn eval(p: &Prop, val: &HashMap<Prop, bool>) -> bool {
+    match p {
+        Prop::Var(_) | Prop::Atom(_) => val[p],
+        Prop::Not(q) => !eval(q, val),
+        Prop::Imply(q, r) => !eval(q, val) || eval(r, val),
+    }
+}
+
+/// The most atoms a formula may have. The proof grows as `2^n` and repeats
+/// the lemmas in every case; at this size a release build checks it in a
+/// fraction of a second, and each further atom about doubles that
+pub const MAX_ATOMS: usize = 4;
+
+#[derive(Debug)]
+pub enum Error {
+    /// A valuation of the atoms under which the formula is false
+    NotTautology { valuation: Vec<(Prop, bool)> },
+    /// The formula has more than [`MAX_ATOMS`] atoms
+    TooManyAtoms { atoms: usize },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NotTautology { valuation } => {
+                write!(f, "Not a tautology, false when")?;
+                for (i, (atom, value)) in valuation.iter().enumerate() {
+                    let sep = if i == 0 { "" } else { "," };
+                    write!(f, "{} {} = {}", sep, atom, value)?;
+                }
+                Ok(())
+            }
+            Error::TooManyAtoms { atoms } => write!(
+                f,
+                "Too many atoms: {}, at most {} are supported",
+                atoms, MAX_ATOMS
+            ),
+        }
+    }
+}
+
+impl StdError for Error {}
+
+/// The first valuation, in binary counting order with the first atom as the
+/// highest bit, under which `p` is false
+pub fn counter_valuation(p: &Prop) -> Result<Option<Vec<(Prop, bool)>>, Error> {
+    let atoms = atoms(p);
+    let n = atoms.len();
+    if n > MAX_ATOMS {
+        return Err(Error::TooManyAtoms { atoms: n });
+    }
+    Ok((0..1usize << n).rev().find_map(|bits| {
+        let val: Vec<_> = atoms
+            .iter()
+            .enumerate()
+            .map(|(i, x)| (x.clone(), bits >> (n - 1 - i) & 1 == 1))
+            .collect();
+        let map = val.iter().cloned().collect();
+        (!eval(p, &map)).then_some(val)
+    }))
+}
+
+/// A proof of `p` from `sys::l1`–`sys::l3`, if it is a tautology with at most
+/// [`MAX_ATOMS`] atoms
+pub fn prove_tautology(p: &Prop) -> Result<Deduction, Error> {
+    if let Some(valuation) = counter_valuation(p)? {
+        return Err(Error::NotTautology { valuation });
+    }
+    let proof = cases(p, &atoms(p), &mut HashMap::new());
+    Ok(Deduction::new(&proof).expect("the proof of a tautology is well formed"))
+}
+
+/// `p` under the assumptions `val`, by case analysis on the other `atoms`
+fn cases(p: &Prop, atoms: &[Prop], val: &mut HashMap<Prop, bool>) -> Proof {
+    let Some((a, rest)) = atoms.split_first() else {
+        let (value, proof) = kalmar(p, val);
+        assert!(value, "cases are only taken on tautologies");
+        return proof;
+    };
+    let mut branch = |value| {
+        val.insert(a.clone(), value);
+        let proof = cases(p, rest, val);
+        val.remove(a);
+        proof
+    };
+    let (pos, neg) = (branch(true), branch(false));
+    by_cases(
+        a,
+        p,
+        Proof::discharge(a, pos),
+        Proof::discharge(&a.not(), neg),
+    )
+}
+
+/// The value of `p` under `val`, and a proof of `p'` from the assumptions
+fn kalmar(p: &Prop, val: &HashMap<Prop, bool>) -> (bool, Proof) {
+    match p {
+        Prop::Var(_) | Prop::Atom(_) => {
+            let value = val[p];
+            let hyp = if value { p.clone() } else { p.not() };
+            (value, Proof::hyp(&hyp))
+        }
+        Prop::Not(q) => match kalmar(q, val) {
+            (true, proof) => (false, dni(q).mp(proof)),
+            (false, proof) => (true, proof),
+        },
+        Prop::Imply(q, r) => {
+            let (vr, pr) = kalmar(r, val);
+            if vr {
+                return (true, Proof::l1(r, q).mp(pr));
+            }
+            match kalmar(q, val) {
+                (false, pq) => (true, efq(q, r).mp(pq)),
+                (true, pq) => (false, imp_false(q, r).mp(pq).mp(pr)),
+            }
+        }
+    }
+}
+
+/// `!a -> (a -> b)`
+fn efq(a: &Prop, b: &Prop) -> Proof {
+    let nb_na = Proof::l1(&a.not(), &b.not()).mp(Proof::hyp(&a.not()));
+    Proof::discharge(&a.not(), Proof::l3(b, a).mp(nb_na))
+}
+
+/// `!!a -> a`
+fn dne(a: &Prop) -> Proof {
+    let nna = a.not().not();
+    let na_nnna = efq(&a.not(), &nna.not()).mp(Proof::hyp(&nna));
+    let body = Proof::l3(a, &nna).mp(na_nnna).mp(Proof::hyp(&nna));
+    Proof::discharge(&nna, body)
+}
+
+/// `a -> !!a`
+fn dni(a: &Prop) -> Proof {
+    Proof::l3(&a.not().not(), a).mp(dne(&a.not()))
+}
+
+/// `!a` from the proofs of `a -> b` and `!b`
+fn modus_tollens(a: &Prop, b: &Prop, ab: Proof, nb: Proof) -> Proof {
+    let nna = a.not().not();
+    let bb = dni(b).mp(ab.mp(dne(a).mp(Proof::hyp(&nna))));
+    Proof::l3(&a.not(), &b.not())
+        .mp(Proof::discharge(&nna, bb))
+        .mp(nb)
+}
+
+/// `a -> (!b -> !(a -> b))`
+fn imp_false(a: &Prop, b: &Prop) -> Proof {
+    let ab = a.imply(b);
+    let nnab = ab.not().not();
+    let b_ = dne(&ab).mp(Proof::hyp(&nnab)).mp(Proof::hyp(a));
+    let na = efq(b, &a.not()).mp(Proof::hyp(&b.not())).mp(b_);
+    let body = Proof::l3(&ab.not(), a)
+        .mp(Proof::discharge(&nnab, na))
+        .mp(Proof::hyp(a));
+    Proof::discharge(a, Proof::discharge(&b.not(), body))
+}
+
+/// `p` from the proofs of `a -> p` and `!a -> p`
+fn by_cases(a: &Prop, p: &Prop, pos: Proof, neg: Proof) -> Proof {
+    let np = p.not();
+    let na = modus_tollens(a, p, pos, Proof::hyp(&np));
+    let n_neg = imp_false(&a.not(), p).mp(na).mp(Proof::hyp(&np));
+    let neg_p = Proof::l3(p, &a.not().imply(p)).mp(Proof::discharge(&np, n_neg));
+    neg_p.mp(neg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        kit::{Expression, Forall},
+        vm::{Assumption, Verifier},
+    };
+
+    #[test]
+    fn test_prove() {
+        let mut vm: Verifier = Verifier::default();
+        type Formula = fn(&Prop, &Prop) -> Prop;
+        let cases: [(&str, Formula); 4] = [
+            // Peirce's law
+            ("peirce", |a, b| a.imply(b).imply(a).imply(a)),
+            ("dne", |a, _| a.not().not().imply(a)),
+            ("contra", |a, b| a.imply(b).imply(&b.not().imply(&a.not()))),
+            ("or_comm", |a, b| a.not().imply(b).imply(&b.not().imply(a))),
+        ];
+        for (name, f) in cases {
+            Forall::new(2, |v| {
+                let p = f(&v[0].into(), &v[1].into());
+                Box::new(prove_tautology(&p).unwrap())
+            })
+            .export(&mut vm, name.into(), false)
+            .unwrap();
+            let found = vm.assumptions(name).unwrap();
+            assert!(
+                found.iter().all(|x| match x {
+                    Assumption::Axiom(_) | Assumption::Object(_) => true,
+                    Assumption::Hypothesis(h) => h == name,
+                    Assumption::Sat(_) => false,
+                }),
+                "{}",
+                name
+            );
+        }
+        assert_eq!(
+            vm.show("peirce").unwrap(),
+            "∀a. ∀b. sys::imply(sys::imply(sys::imply(a, b), a), a)"
+        );
+
+        // over symbols, without a binder
+        crate::asm::run(&mut vm, "obj 0 p").unwrap();
+        let p = Prop::atom("p");
+        prove_tautology(&p.imply(&p))
+            .unwrap()
+            .export(&mut vm, "p_p".into(), false)
+            .unwrap();
+        assert_eq!(vm.show("p_p").unwrap(), "sys::imply(p, p)");
+    }
+
+    #[test]
+    fn test_not_tautology() {
+        let (a, b) = (Prop::atom("a"), Prop::atom("b"));
+        let Err(err) = prove_tautology(&a.imply(&b.imply(&a)).imply(&b)) else {
+            panic!()
+        };
+        let Error::NotTautology { valuation } = &err else {
+            panic!("{}", err)
+        };
+        assert_eq!(*valuation, [(a.clone(), true), (b.clone(), false)]);
+        assert_eq!(
+            err.to_string(),
+            "Not a tautology, false when a = true, b = false"
+        );
+        assert!(prove_tautology(&a).is_err());
+    }
+
+    #[test]
+    fn test_too_many_atoms() {
+        // p0 -> p1 -> ... -> p(n - 1) -> p0
+        let imply_all = |n: usize| {
+            let body = (1..n).rev().fold(Prop::atom("p0"), |acc, i| {
+                Prop::atom(&format!("p{}", i)).imply(&acc)
+            });
+            Prop::atom("p0").imply(&body)
+        };
+        // the largest formula is still proven and checked
+        let mut vm: Verifier = Verifier::default();
+        for i in 0..MAX_ATOMS {
+            crate::asm::run(&mut vm, &format!("obj 0 p{}", i)).unwrap();
+        }
+        prove_tautology(&imply_all(MAX_ATOMS))
+            .unwrap()
+            .export(&mut vm, "imply_all".into(), false)
+            .unwrap();
+        let found = vm.assumptions("imply_all").unwrap();
+        assert!(found.iter().all(|x| match x {
+            Assumption::Axiom(_) | Assumption::Object(_) => true,
+            Assumption::Hypothesis(h) => h == "imply_all",
+            Assumption::Sat(_) => false,
+        }));
+        for n in [MAX_ATOMS + 1, 64, 100] {
+            assert!(matches!(
+                prove_tautology(&imply_all(n)),
+                Err(Error::TooManyAtoms { atoms }) if atoms == n
+            ));
+        }
+    }
+}
@@ -2,9 +2,11 @@ use std::hash::Hash;
 
 pub trait IdGenerator {
     type Id: Clone + Hash + Ord;
+    #[allow(clippy::wrong_self_convention)]
     fn new(&mut self) -> Self::Id;
 }
 
+#[derive(Default)]
 pub struct CountGenerator(usize);
 impl IdGenerator for CountGenerator {
     type Id = usize;
@@ -14,21 +16,11 @@ impl IdGenerator for CountGenerator {
         id
     }
 }
-impl Default for CountGenerator {
-    fn default() -> Self {
-        Self(0)
-    }
-}
 
 pub fn rc_take<T: Clone>(rc: std::rc::Rc<T>) -> T {
     std::rc::Rc::try_unwrap(rc).unwrap_or_else(|rc| rc.as_ref().clone())
 }
 
-pub fn vec_rev_get<T>(arr: &Vec<T>, index: usize) -> Option<&T> {
-    let len = arr.len();
-    if index <= len {
-        Some(&arr[len - index])
-    } else {
-        None
-    }
+pub fn vec_rev_get<T>(arr: &rpds::Vector<T>, index: usize) -> Option<&T> {
+    arr.len().checked_sub(index).and_then(|i| arr.get(i))
 }
@@ -1,20 +1,42 @@
+mod audit;
+mod checkpoint;
+mod image;
+mod library;
+mod limits;
+mod memo;
+mod model;
+mod show;
+mod terms;
 mod ty;
 
+pub use audit::{Assumption, SymbolKind};
+pub use checkpoint::Checkpoint;
+pub use image::ImageError;
+pub use library::{FinishError, Leftover, Library};
+pub use limits::{Limit, Limits};
+pub use model::{Invalid, ModelReport};
+pub use ty::Type;
+
 use std::{
-    cell::{RefCell, RefMut},
+    cell::RefCell,
     cmp::max,
-    collections::HashMap,
+    collections::HashSet,
     num::NonZeroUsize,
-    ops::{Deref, DerefMut},
+    ops::Deref,
     rc::Rc,
+    time::{Duration, Instant},
 };
 
+use rpds::{HashTrieMap, HashTrieSet, Vector};
+
 use crate::{
-    err::{OperationError, Result},
-    isa::InstructionSet,
-    kit::{imply, not, Expression, Forall},
+    ds::pds::set_union,
+    err::{OperationError, Result, StackKind},
+    isa::{InstructionSet, Recover},
+    kernel::{Classical, Kernel},
     util::{vec_rev_get, CountGenerator, IdGenerator},
 };
+use limits::Fuel;
 
 enum Element<G: IdGenerator, P: Clone> {
     Object { id: G::Id, args: Vec<P> },
@@ -61,6 +83,10 @@ struct TypedElement<G: IdGenerator> {
     data: RefCell<CacheEnum<G, Rc<Self>>>,
     max_ref: usize,
     ty: ty::Type,
+    /// Shared through [`terms::Terms`]
+    canonical: bool,
+    /// Nodes of the term, counting repeats, if canonical
+    size: usize,
 }
 
 fn max_ref_shift(max_ref: usize, delta: usize) -> usize {
@@ -71,142 +97,364 @@ fn max_ref_shift(max_ref: usize, delta: usize) -> usize {
     }
 }
 
-struct CacheFlusher<'a, G: IdGenerator> {
-    arg_stack: Vec<(Option<Rc<TypedElement<G>>>, usize)>,
+/// How a binder of the term being flushed appears in the output
+enum Binder<G: IdGenerator> {
+    /// Kept as a binder, opened at the given output depth
+    Var(usize),
+    /// Substituted by a flushed value, valid at the given output depth
+    Val(Rc<TypedElement<G>>, usize),
+}
+
+enum Lookup<'b, G: IdGenerator> {
+    Index(usize),
+    Value(&'b Rc<TypedElement<G>>, usize),
+}
+
+/// The result of flushing a node, `None` if it is unchanged
+type Flushed<G> = Option<Rc<TypedElement<G>>>;
+
+/// The state of a [`CacheFlusher`] set aside while a substituted value is
+/// flushed
+struct Saved<G: IdGenerator> {
+    arg_stack: Vec<Binder<G>>,
     bind_stack: Vec<Rc<TypedElement<G>>>,
+    depth: usize,
     ref_shift: usize,
+}
+
+/// Pending work of [`CacheFlusher::flush_enum`]. A task that flushes a node
+/// pushes one [`Flushed`] result; the others continue a node once the results
+/// of its parts are there.
+enum Task<G: IdGenerator> {
+    /// Flush a node, or just shift it if nothing is bound inside
+    Ptr(Rc<TypedElement<G>>, bool),
+    /// Flush a node
+    Enum(Rc<TypedElement<G>>, bool),
+    /// Rebuild a variable from its flushed arguments
+    Variable {
+        pos: NonZeroUsize,
+        args: Vec<Rc<TypedElement<G>>>,
+        ty: ty::Type,
+        force: bool,
+        binds: Vec<Rc<TypedElement<G>>>,
+    },
+    /// Rebuild an object from its flushed arguments
+    Object {
+        id: G::Id,
+        args: Vec<Rc<TypedElement<G>>>,
+        binds: Vec<Rc<TypedElement<G>>>,
+    },
+    /// Leave the body of a universal that was applied
+    Substituted(Rc<TypedElement<G>>),
+    /// Leave the body of a universal kept as a binder
+    Opened,
+    /// Apply the function of a bind to its flushed argument
+    BindArg {
+        func: Rc<TypedElement<G>>,
+        arg: Rc<TypedElement<G>>,
+        force: bool,
+        binds: Vec<Rc<TypedElement<G>>>,
+    },
+    /// Leave the function of a bind
+    BindFunc(Rc<TypedElement<G>>),
+    /// Restore the state after flushing a substituted value
+    Value {
+        val: Rc<TypedElement<G>>,
+        saved: Saved<G>,
+    },
+    /// Leave the node under a shift
+    Shifted {
+        el: Rc<TypedElement<G>>,
+        hidden: Vec<Binder<G>>,
+        ref_shift: usize,
+    },
+}
+
+struct CacheFlusher<'a, G: IdGenerator> {
+    arg_stack: Vec<Binder<G>>,
+    bind_stack: Vec<Rc<TypedElement<G>>>, // top is applied first
+    depth: usize,
+    ref_shift: usize, // applied to references beyond arg_stack
     ty_reg: &'a mut ty::Registry,
+    fuel: &'a mut Fuel,
 }
 
 impl<'a, G: IdGenerator> CacheFlusher<'a, G> {
-    fn new(ty_reg: &'a mut ty::Registry) -> Self {
+    fn new(ty_reg: &'a mut ty::Registry, fuel: &'a mut Fuel) -> Self {
         Self {
             arg_stack: Vec::new(),
             bind_stack: Vec::new(),
+            depth: 0,
             ref_shift: 0,
             ty_reg,
+            fuel,
+        }
+    }
+
+    fn lookup(&self, pos: usize) -> Lookup<'_, G> {
+        let len = self.arg_stack.len();
+        if pos > len {
+            return Lookup::Index(self.depth + self.ref_shift + pos - len);
+        }
+        match &self.arg_stack[len - pos] {
+            Binder::Var(depth) => Lookup::Index(self.depth - depth),
+            Binder::Val(val, depth) => Lookup::Value(val, self.depth - depth),
         }
     }
 
+    /// Flush the node, returning `None` if it is unchanged.
+    /// With `force`, the result is always a primitive.
+    ///
+    /// The term is walked with an explicit stack of [`Task`]s, so its depth is
+    /// only bounded by memory. Each task takes a step of the fuel.
     fn flush_enum(
         &mut self,
-        data: &CacheEnum<G, Rc<TypedElement<G>>>,
-    ) -> Option<Rc<TypedElement<G>>> {
+        node: &Rc<TypedElement<G>>,
+        force: bool,
+    ) -> Result<Flushed<G>> {
+        let mut tasks = vec![Task::Enum(node.clone(), force)];
+        let mut results = Vec::new();
+        while let Some(task) = tasks.pop() {
+            self.fuel.burn()?;
+            self.step(task, &mut tasks, &mut results)?;
+        }
+        Ok(results.pop().expect("every flushed node leaves a result"))
+    }
+
+    /// Run a task, pushing the tasks it needs before it can finish
+    fn step(
+        &mut self,
+        task: Task<G>,
+        tasks: &mut Vec<Task<G>>,
+        results: &mut Vec<Flushed<G>>,
+    ) -> Result<()> {
         use CacheEnum::*;
         use Element::*;
-        match data {
-            Primitive(Variable { pos, args }) => {
-                let pos = pos.get() + self.ref_shift;
-                if let Some((Some(val), pre_binded_cnt)) = vec_rev_get(&self.arg_stack, pos) {
-                    let binded_cnt = self.tot_bind_cnt() - pre_binded_cnt + 1;
-                    let val = Rc::new(val.set_shift(pos - binded_cnt)?);
-                    let args: Vec<_> = args
-                        .iter()
-                        .map(|x| self.flush_ptr(x).unwrap_or_else(|| x.clone()))
-                        .collect();
-                    for arg in args.iter().rev() {
-                        self.bind_stack.push(arg.clone());
+        match task {
+            Task::Ptr(ptr, force) => {
+                let is_primitive = matches!(ptr.data.borrow().deref(), Primitive(..));
+                if self.bind_stack.is_empty() && (is_primitive || !force) {
+                    if let Some(shift) = self.uniform_shift(ptr.max_ref) {
+                        if shift == 0 || !force {
+                            results.push(ptr.set_shift(shift).map(Rc::new));
+                            return Ok(());
+                        }
                     }
-                    let el = self.flush_ptr(&val).unwrap_or(val);
-                    for _ in 0..args.len() {
-                        self.bind_stack.pop().unwrap();
+                }
+                tasks.push(Task::Enum(ptr, force));
+            }
+            Task::Enum(node, force) => {
+                let data = node.data.borrow().clone();
+                match data {
+                    Primitive(Variable { pos, args }) => {
+                        let binds = std::mem::take(&mut self.bind_stack);
+                        let args_tasks = args.iter().rev().map(|x| Task::Ptr(x.clone(), false));
+                        let ty = node.ty.clone();
+                        tasks.push(Task::Variable {
+                            pos,
+                            args: args.clone(),
+                            ty,
+                            force,
+                            binds,
+                        });
+                        tasks.extend(args_tasks);
                     }
-                    Some(el)
-                } else {
-                    let new_args: Vec<_> = args.iter().map(|x| self.flush_ptr(x)).collect();
-                    let new_pos = self.calc_new_ref(pos);
-                    if pos == new_pos
-                        && self.bind_stack.is_empty()
-                        && new_args.iter().all(|x| x.is_none())
-                    {
-                        None
-                    } else {
-                        Some(Rc::new(TypedElement::new_primitive(
-                            Element::Variable {
+                    Primitive(Object { id, args }) => {
+                        let binds = std::mem::take(&mut self.bind_stack);
+                        let args_tasks = args.iter().rev().map(|x| Task::Ptr(x.clone(), false));
+                        tasks.push(Task::Object {
+                            id,
+                            args: args.clone(),
+                            binds,
+                        });
+                        tasks.extend(args_tasks);
+                    }
+                    Primitive(Universal { body }) => {
+                        if let Some(v) = self.bind_stack.pop() {
+                            self.arg_stack.push(Binder::Val(v, self.depth));
+                            tasks.push(Task::Substituted(body.clone()));
+                            tasks.push(Task::Ptr(body, force));
+                        } else {
+                            self.arg_stack.push(Binder::Var(self.depth));
+                            self.depth += 1;
+                            tasks.push(Task::Opened);
+                            tasks.push(Task::Ptr(body, false));
+                        }
+                    }
+                    Bind { func, arg } => {
+                        let binds = std::mem::take(&mut self.bind_stack);
+                        tasks.push(Task::BindArg {
+                            func,
+                            arg: arg.clone(),
+                            force,
+                            binds,
+                        });
+                        tasks.push(Task::Ptr(arg, false));
+                    }
+                    RefShift(el, delta) => {
+                        let delta = delta.get();
+                        let len = self.arg_stack.len();
+                        let hidden = self.arg_stack.split_off(len - delta.min(len));
+                        let ref_shift = self.ref_shift;
+                        self.ref_shift += delta - hidden.len();
+                        tasks.push(Task::Shifted {
+                            el: el.clone(),
+                            hidden,
+                            ref_shift,
+                        });
+                        tasks.push(Task::Ptr(el, force));
+                    }
+                }
+            }
+            Task::Variable {
+                pos,
+                args,
+                ty,
+                force,
+                binds,
+            } => {
+                let new_args = results.split_off(results.len() - args.len());
+                self.bind_stack = binds;
+                let bind_cnt = self.bind_stack.len();
+                let args = new_args
+                    .into_iter()
+                    .zip(args.iter())
+                    .map(|(x, orig)| (x.is_some(), x.unwrap_or_else(|| orig.clone())))
+                    .collect::<Vec<_>>();
+                let changed = args.iter().any(|x| x.0);
+                let args = args
+                    .into_iter()
+                    .map(|x| x.1)
+                    .chain(self.bind_stack.iter().rev().cloned());
+                let val = match self.lookup(pos.get()) {
+                    Lookup::Index(new_pos) => Err(new_pos),
+                    Lookup::Value(val, delta) => Ok((val.clone(), delta)),
+                };
+                match val {
+                    Err(new_pos) => {
+                        if new_pos == pos.get() && bind_cnt == 0 && !changed {
+                            results.push(None);
+                            return Ok(());
+                        }
+                        let args: Vec<_> = args.collect();
+                        let ty = self
+                            .bind_stack
+                            .iter()
+                            .rev()
+                            .try_fold(ty, |ty, x| ty.apply(&x.ty))?;
+                        results.push(Some(Rc::new(TypedElement::new_primitive(
+                            Variable {
                                 pos: new_pos.try_into().unwrap(),
-                                args: new_args
-                                    .iter()
-                                    .zip(args.iter())
-                                    .map(|(x, orig)| x.as_ref().unwrap_or_else(|| orig))
-                                    .chain(self.bind_stack.iter())
-                                    .cloned()
-                                    .collect(),
+                                args,
                             },
-                            self.ty_reg.symbol(),
-                        )))
+                            ty,
+                        ))));
+                    }
+                    Ok((val, delta)) => {
+                        // Flush the value as a separate flusher would
+                        let mut bind_stack: Vec<_> = args.collect();
+                        bind_stack.reverse();
+                        let saved = Saved {
+                            arg_stack: std::mem::take(&mut self.arg_stack),
+                            bind_stack: std::mem::replace(&mut self.bind_stack, bind_stack),
+                            depth: std::mem::replace(&mut self.depth, 0),
+                            ref_shift: std::mem::replace(&mut self.ref_shift, delta),
+                        };
+                        tasks.push(Task::Value {
+                            val: val.clone(),
+                            saved,
+                        });
+                        tasks.push(Task::Ptr(val, force));
                     }
                 }
             }
-            Primitive(Object { id, args }) => {
-                let new_args: Vec<_> = args.iter().map(|x| self.flush_ptr(x)).collect();
+            Task::Object { id, args, binds } => {
+                let new_args = results.split_off(results.len() - args.len());
+                self.bind_stack = binds;
                 if new_args.iter().all(|x| x.is_none()) {
-                    return None;
+                    results.push(None);
+                    return Ok(());
                 }
-                Some(Rc::new(new_object(
-                    &mut self.ty_reg,
-                    id.clone(),
+                results.push(Some(Rc::new(new_object(
+                    self.ty_reg,
+                    id,
                     new_args
                         .into_iter()
                         .zip(args.iter())
                         .map(|(x, orig)| x.unwrap_or_else(|| orig.clone()))
                         .collect(),
-                )))
+                ))));
             }
-            Primitive(Universal { body }) => {
-                self.arg_stack.push(if let Some(v) = self.bind_stack.pop() {
-                    (Some(v), self.tot_bind_cnt() + 1)
-                } else {
-                    (None, self.tot_bind_cnt())
-                });
-                let el = self.flush_ptr(body);
-                if let (Some(v), _) = self.arg_stack.pop().unwrap() {
+            Task::Substituted(body) => {
+                let el = results.pop().unwrap();
+                if let Some(Binder::Val(v, _)) = self.arg_stack.pop() {
                     self.bind_stack.push(v);
                 }
-                el
+                results.push(Some(el.unwrap_or(body)));
+            }
+            Task::Opened => {
+                self.depth -= 1;
+                self.arg_stack.pop();
+                let el = results.pop().unwrap().map(|el| {
+                    let sym = self.ty_reg.symbol();
+                    let ty = self.ty_reg.infer(sym, el.ty.clone());
+                    Rc::new(TypedElement::new_primitive(Universal { body: el }, ty))
+                });
+                results.push(el);
+            }
+            Task::BindArg {
+                func,
+                arg,
+                force,
+                binds,
+            } => {
+                let new_arg = results.pop().unwrap();
+                self.bind_stack = binds;
+                self.bind_stack.push(new_arg.unwrap_or(arg));
+                tasks.push(Task::BindFunc(func.clone()));
+                tasks.push(Task::Ptr(func, force));
             }
-            Bind { func, arg } => {
-                let mut bind_stack = Vec::new();
-                std::mem::swap(&mut bind_stack, &mut self.bind_stack);
-                let arg = self.flush_ptr(arg).unwrap_or_else(|| arg.clone());
-                std::mem::swap(&mut bind_stack, &mut self.bind_stack);
-                self.bind_stack.push(arg);
-                let el = self.flush_ptr(func);
+            Task::BindFunc(func) => {
+                let el = results.pop().unwrap();
                 self.bind_stack.pop().unwrap();
-                el
+                results.push(Some(el.unwrap_or(func)));
             }
-            RefShift(el, delta) => {
-                self.ref_shift += delta.get();
-                let el = self.flush_ptr(el);
-                self.ref_shift -= delta.get();
-                el
+            Task::Value { val, saved } => {
+                let el = results.pop().unwrap();
+                self.arg_stack = saved.arg_stack;
+                self.bind_stack = saved.bind_stack;
+                self.depth = saved.depth;
+                self.ref_shift = saved.ref_shift;
+                results.push(Some(el.unwrap_or(val)));
+            }
+            Task::Shifted {
+                el,
+                hidden,
+                ref_shift,
+            } => {
+                let ret = results.pop().unwrap();
+                self.ref_shift = ref_shift;
+                self.arg_stack.extend(hidden);
+                results.push(Some(ret.unwrap_or(el)));
             }
         }
+        Ok(())
     }
 
-    fn tot_bind_cnt(&self) -> usize {
-        self.arg_stack.last().map_or(0, |x| x.1)
-    }
-
-    fn calc_new_ref(&self, pos: usize) -> usize {
-        let pos = max_ref_shift(pos, self.ref_shift);
-        let binded_cnt = vec_rev_get(&self.arg_stack, pos + 1).map_or(0, |x| x.1);
-        pos - (self.tot_bind_cnt() - binded_cnt)
-    }
-
-    fn flush_ptr(&mut self, ptr: &Rc<TypedElement<G>>) -> Option<Rc<TypedElement<G>>> {
-        if self.bind_stack.is_empty() && ptr.max_ref == self.calc_new_ref(ptr.max_ref) {
-            return ptr.set_shift(self.ref_shift).map(|x| Rc::new(x));
+    /// If all references of the node are shifted by the same amount
+    fn uniform_shift(&self, max_ref: usize) -> Option<usize> {
+        let mut shift = None;
+        for pos in 1..=max_ref {
+            match self.lookup(pos) {
+                Lookup::Index(new_pos) if new_pos >= pos => {
+                    if shift.get_or_insert(new_pos - pos) != &(new_pos - pos) {
+                        return None;
+                    }
+                }
+                _ => return None,
+            }
         }
-        let data = ptr.data.borrow();
-        self.flush_enum(data.deref())
-    }
-}
-
-impl<'a, G: IdGenerator> Drop for CacheFlusher<'a, G> {
-    fn drop(&mut self) {
-        assert!(self.arg_stack.is_empty());
-        assert!(self.bind_stack.is_empty());
-        assert!(self.ref_shift == 0);
+        Some(shift.unwrap_or(0))
     }
 }
 
@@ -223,89 +471,125 @@ impl<G: IdGenerator> TypedElement<G> {
                 data: RefCell::new(CacheEnum::RefShift(p.clone(), v)),
                 max_ref: max_ref_shift(p.max_ref, v.get()),
                 ty: p.ty.clone(),
+                canonical: false,
+                size: 0,
             })
         } else {
             None
         }
     }
 
-    fn unwrap_one<'a>(
-        self: &'a mut Rc<Self>,
+    /// Flush the node until its head is a primitive
+    fn unwrap_one(
+        self: &mut Rc<Self>,
         ty_reg: &mut ty::Registry,
-    ) -> RefMut<'a, Element<G, Rc<Self>>> {
+        fuel: &mut Fuel,
+    ) -> Result<Element<G, Rc<Self>>> {
         use CacheEnum::*;
-        loop {
-            let mut data_mut = self.data.borrow_mut();
-            match data_mut.deref() {
-                Primitive(..) => break,
-                _ => (),
-            }
-            let el = CacheFlusher::new(ty_reg).flush_enum(data_mut.deref_mut());
-            if let Some(el) = el {
-                assert_eq!(el.ty, self.ty);
-                assert_eq!(el.max_ref, self.max_ref);
-                std::mem::drop(data_mut);
-                *self = el;
-            }
+        if !matches!(self.data.borrow().deref(), Primitive(..)) {
+            let el = CacheFlusher::new(ty_reg, fuel).flush_enum(self, true)?;
+            *self = el.expect("flushing a cache always makes progress");
         }
-        RefMut::map(self.data.borrow_mut(), |x| match x {
-            Primitive(el) => el,
+        match self.data.borrow().deref() {
+            Primitive(el) => Ok(el.clone()),
             _ => unreachable!(),
-        })
+        }
     }
 
-    fn check_equal(a: &mut Rc<Self>, b: &mut Rc<Self>, ty_reg: &mut ty::Registry) -> bool {
+    /// Structural equality, flushing both sides as far as needed. Pairs
+    /// found equal or unequal before are looked up in `memo`, and a pair met
+    /// twice is compared once, so shared parts cost their graph and not their
+    /// tree.
+    fn check_equal(
+        a: &Rc<Self>,
+        b: &Rc<Self>,
+        ty_reg: &mut ty::Registry,
+        memo: &mut memo::EqMemo<G>,
+        fuel: &mut Fuel,
+    ) -> Result<bool> {
         if Rc::ptr_eq(a, b) {
-            return true;
-        }
-        if (a.ty != b.ty) || (a.max_ref != b.max_ref) {
-            return false;
+            return Ok(true);
         }
         use Element::*;
-        match (
-            a.unwrap_one(ty_reg).deref_mut(),
-            b.unwrap_one(ty_reg).deref_mut(),
-        ) {
-            (
-                Object {
-                    id: id1,
-                    args: params1,
-                },
-                Object {
-                    id: id2,
-                    args: params2,
-                },
-            ) => {
-                if id1 != id2 {
-                    return false;
-                }
-                assert!(params1.len() == params2.len());
-                params1
-                    .iter_mut()
-                    .zip(params2.iter_mut())
-                    .all(|(x, y)| Self::check_equal(x, y, ty_reg))
+        let mut todo = vec![(a.clone(), b.clone())];
+        // Pairs met before, kept alive by `seen` so their addresses stay valid
+        let mut visited = HashSet::new();
+        let mut seen = Vec::new();
+        let unequal = |memo: &mut memo::EqMemo<G>| {
+            memo.set_unequal(a, b);
+            Ok(false)
+        };
+        while let Some((x, y)) = todo.pop() {
+            if Rc::ptr_eq(&x, &y) {
+                continue;
+            }
+            match memo.get(&x, &y) {
+                Some(true) => continue,
+                Some(false) => return unequal(memo),
+                None => (),
             }
-            (Universal { body: body1 }, Universal { body: body2 }) => {
-                Self::check_equal(body1, body2, ty_reg)
+            if !visited.insert((Rc::as_ptr(&x), Rc::as_ptr(&y))) {
+                continue;
             }
-            (
-                Variable {
-                    pos: pos1,
-                    args: args1,
-                },
-                Variable {
-                    pos: pos2,
-                    args: args2,
-                },
-            ) => {
-                pos1 == pos2
-                    && args1.len() == args2.len()
-                    && args1
-                        .iter_mut()
-                        .zip(args2.iter_mut())
-                        .all(|(x, y)| Self::check_equal(x, y, ty_reg))
+            fuel.burn()?;
+            seen.push((x.clone(), y.clone()));
+            let (mut x, mut y) = (x, y);
+            match (x.unwrap_one(ty_reg, fuel)?, y.unwrap_one(ty_reg, fuel)?) {
+                (
+                    Object {
+                        id: id1,
+                        args: args1,
+                    },
+                    Object {
+                        id: id2,
+                        args: args2,
+                    },
+                ) => {
+                    if id1 != id2 {
+                        return unequal(memo);
+                    }
+                    assert!(args1.len() == args2.len());
+                    todo.extend(args1.into_iter().zip(args2).rev());
+                }
+                (Universal { body: body1 }, Universal { body: body2 }) => {
+                    todo.push((body1, body2));
+                }
+                (
+                    Variable {
+                        pos: pos1,
+                        args: args1,
+                    },
+                    Variable {
+                        pos: pos2,
+                        args: args2,
+                    },
+                ) => {
+                    if pos1 != pos2 || args1.len() != args2.len() {
+                        return unequal(memo);
+                    }
+                    todo.extend(args1.into_iter().zip(args2).rev());
+                }
+                _ => return unequal(memo),
             }
-            _ => false,
+        }
+        for (x, y) in &seen {
+            memo.set_equal(x, y);
+        }
+        Ok(true)
+    }
+
+    /// Move the parts out, leaving a leaf
+    fn take_parts(&mut self) -> Vec<Rc<Self>> {
+        use CacheEnum::*;
+        let leaf = Primitive(Element::Variable {
+            pos: NonZeroUsize::MIN,
+            args: Vec::new(),
+        });
+        match std::mem::replace(self.data.get_mut(), leaf) {
+            Primitive(Element::Object { args, .. } | Element::Variable { args, .. }) => args,
+            Primitive(Element::Universal { body }) => vec![body],
+            Bind { func, arg } => vec![func, arg],
+            RefShift(el, _) => vec![el],
         }
     }
 
@@ -322,6 +606,8 @@ impl<G: IdGenerator> TypedElement<G> {
             data: RefCell::new(CacheEnum::Primitive(el)),
             ty,
             max_ref,
+            canonical: false,
+            size: 0,
         }
     }
 
@@ -342,117 +628,255 @@ impl<G: IdGenerator> TypedElement<G> {
             data: RefCell::new(CacheEnum::Bind { func: self, arg }),
             max_ref,
             ty,
+            canonical: false,
+            size: 0,
         })
     }
 }
 
+/// Parts only owned by the dropped node are dropped in a loop, so that a deep
+/// term does not overflow the stack
+impl<G: IdGenerator> Drop for TypedElement<G> {
+    fn drop(&mut self) {
+        let mut todo = self.take_parts();
+        while let Some(el) = todo.pop() {
+            if let Ok(mut el) = Rc::try_unwrap(el) {
+                todo.extend(el.take_parts());
+            }
+        }
+    }
+}
+
+type ElementPtr<G> = Rc<TypedElement<G>>;
+
+/// Something an element was built from
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Dep {
+    Symbol(Rc<str>),
+    /// A symbol only used as a term, through the argument of `app`
+    Term(Rc<str>),
+    Sat,
+}
+
+impl Dep {
+    /// The dependency of a term built from an element with this dependency
+    fn as_term(&self) -> Option<Dep> {
+        match self {
+            Dep::Symbol(s) | Dep::Term(s) => Some(Dep::Term(s.clone())),
+            Dep::Sat => None,
+        }
+    }
+}
+
+type Deps = HashTrieSet<Dep>;
+
 enum StackElement<G: IdGenerator> {
     Argument,
     Synthetic,
     Types(Vec<ty::Type>),
-    Element(Rc<TypedElement<G>>),
+    Element(Rc<TypedElement<G>>, Deps),
 }
 
-pub struct Verifier<G: IdGenerator = CountGenerator> {
-    obj_id: G,
-    syn_cnt: usize,
-    arg_stack: Vec<ty::Type>,
-    stack: Vec<StackElement<G>>,
-    ty_reg: ty::Registry,
-    imply_id: G::Id,
-    sym_table: HashMap<String, (bool, Rc<TypedElement<G>>)>, // is_real, element
+impl<G: IdGenerator> Clone for StackElement<G> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Argument => Self::Argument,
+            Self::Synthetic => Self::Synthetic,
+            Self::Types(vec) => Self::Types(vec.clone()),
+            Self::Element(el, deps) => Self::Element(el.clone(), deps.clone()),
+        }
+    }
 }
 
-fn s_pop<T>(s: &mut Vec<T>) -> Result<T> {
-    match s.pop() {
-        Some(x) => Ok(x),
-        None => Err(OperationError::new("Stack underflow")),
-    }
+struct Symbol<G: IdGenerator> {
+    kind: SymbolKind,
+    el: Rc<TypedElement<G>>,
+    deps: Deps,
 }
 
-impl<G: IdGenerator> Verifier<G> {
-    fn set_real(&mut self, name: &str) {
-        self.sym_table.get_mut(name).unwrap().0 = true;
+impl<G: IdGenerator> Clone for Symbol<G> {
+    fn clone(&self) -> Self {
+        Self {
+            kind: self.kind,
+            el: self.el.clone(),
+            deps: self.deps.clone(),
+        }
     }
+}
 
-    fn init_l1(&mut self) -> Result<()> {
-        let name = "sys::l1";
-        Forall::new(2, |args| {
-            let a = args[0];
-            let b = args[1];
-            imply(a.into(), imply(b.into(), a.into()))
-        })
-        .export(self, name.into(), false)?;
-        self.set_real(name);
-        Ok(())
-    }
+type SymbolTable<G> = HashTrieMap<String, Symbol<G>>;
+type ObjectNames<G> = HashTrieMap<<G as IdGenerator>::Id, String>;
 
-    fn init_l2(&mut self) -> Result<()> {
-        let name = "sys::l2";
-        Forall::new(3, |args| {
-            let a = args[0];
-            let b = args[1];
-            let c = args[2];
-            imply(
-                imply(a.into(), imply(b.into(), c.into())),
-                imply(imply(a.into(), b.into()), imply(a.into(), c.into())),
-            )
-        })
-        .export(self, name.into(), false)?;
-        self.set_real(name);
-        Ok(())
+impl<G: IdGenerator> StackElement<G> {
+    fn kind(&self) -> StackKind {
+        match self {
+            StackElement::Argument => StackKind::Argument,
+            StackElement::Synthetic => StackKind::Synthetic,
+            StackElement::Types(_) => StackKind::Types,
+            StackElement::Element(..) => StackKind::Element,
+        }
     }
+}
 
-    fn init_l3(&mut self) -> Result<()> {
-        let name = "sys::l3";
-        Forall::new(2, |args| {
-            let a = args[0];
-            let b = args[1];
-            imply(
-                imply(not(a.into()), not(b.into())),
-                imply(b.into(), a.into()),
-            )
-        })
-        .export(self, name.into(), false)?;
-        self.set_real(name);
-        Ok(())
-    }
+/// The state lives in persistent structures, so that a [`Checkpoint`] is a
+/// cheap copy of it.
+///
+/// Terms are shared through `Rc`, so a verifier stays on the thread that made
+/// it. Independent files are verified in parallel with a verifier per thread.
+pub struct Verifier<G: IdGenerator = CountGenerator> {
+    obj_id: G,
+    syn_cnt: usize,
+    arg_stack: Vector<ty::Type>,
+    stack: Vector<StackElement<G>>,
+    ty_reg: ty::Registry,
+    imply_id: Option<G::Id>,
+    not_id: Option<G::Id>,
+    sym_table: SymbolTable<G>,
+    obj_names: ObjectNames<G>,
+    terms: terms::Terms<G>,
+    eq_memo: memo::EqMemo<G>,
+    limits: Limits,
+    /// When the time budget runs out, and the budget
+    deadline: Option<(Instant, Duration)>,
+    /// Shared with the checkpoints taken from this verifier
+    identity: Rc<()>,
+}
 
-    fn init_sys(&mut self) -> Result<()> {
-        self.obj(1, "sys::not".into())?;
-        self.add_obj(1, "sys::imply".into(), self.imply_id.clone())?;
-        self.init_l1()?;
-        self.init_l2()?;
-        self.init_l3()?;
-        Ok(())
+impl<G: IdGenerator> Verifier<G> {
+    fn set_axiom(&mut self, name: &str) {
+        self.sym_table.get_mut(name).unwrap().kind = SymbolKind::Axiom;
     }
 
-    pub fn new(mut obj_id: G) -> Self {
-        let imply_id = obj_id.new();
+    /// Install the objects and axioms of `kernel`
+    pub fn with_kernel<K: Kernel>(obj_id: G, kernel: &K) -> Result<Self> {
         let mut vm = Self {
             obj_id,
             ty_reg: ty::Registry::new(),
-            stack: Vec::new(),
-            arg_stack: Vec::new(),
+            stack: Vector::new(),
+            arg_stack: Vector::new(),
             syn_cnt: 0,
-            imply_id,
-            sym_table: HashMap::new(),
+            imply_id: None,
+            not_id: None,
+            sym_table: HashTrieMap::new(),
+            obj_names: HashTrieMap::new(),
+            terms: terms::Terms::new(),
+            eq_memo: memo::EqMemo::new(),
+            limits: Limits::default(),
+            deadline: None,
+            identity: Rc::new(()),
         };
-        vm.init_sys().unwrap();
-        vm
+        for (n, name) in kernel.objects() {
+            vm.expect_new_sym(name)?;
+            let id = vm.obj_id.new();
+            for (expected, role, slot) in [
+                (2, kernel.imply(), &mut vm.imply_id),
+                (1, kernel.not(), &mut vm.not_id),
+            ] {
+                if role != Some(name) {
+                    continue;
+                }
+                if n != expected {
+                    return Err(OperationError::KernelArity {
+                        name: name.into(),
+                        expected,
+                        actual: n,
+                    });
+                }
+                *slot = Some(id.clone());
+            }
+            vm.add_obj(n, name.into(), id)?;
+        }
+        for (name, id) in [(kernel.imply(), &vm.imply_id), (kernel.not(), &vm.not_id)] {
+            if let (Some(name), None) = (name, id) {
+                return Err(OperationError::SymbolNotFound { name: name.into() });
+            }
+        }
+        for (name, axiom) in kernel.axioms::<Self>() {
+            axiom.export(&mut vm, name.into(), false)?;
+            vm.set_axiom(name);
+        }
+        Ok(vm)
+    }
+
+    /// A verifier with the [`Classical`] kernel
+    pub fn new(obj_id: G) -> Self {
+        Self::with_kernel(obj_id, &Classical).unwrap()
+    }
+
+    /// Names of all symbols, in no particular order
+    pub fn symbols(&self) -> impl Iterator<Item = &str> {
+        self.sym_table.keys().map(String::as_str)
+    }
+
+    /// Number of `syn` blocks currently open
+    pub fn syn_depth(&self) -> usize {
+        self.syn_cnt
     }
 
     fn push(&mut self, el: StackElement<G>) {
-        self.stack.push(el)
+        self.stack.push_back_mut(el)
+    }
+
+    /// The `n`-th entry from the stack top, counted from 1
+    fn peek(&self, n: usize) -> Result<&StackElement<G>> {
+        let depth = self.stack.len();
+        if n > depth {
+            return Err(OperationError::StackUnderflow { depth });
+        }
+        Ok(&self.stack[depth - n])
     }
 
-    fn pop(&mut self) -> Result<StackElement<G>> {
-        s_pop(&mut self.stack)
+    fn peek_element(&self, n: usize) -> Result<(Rc<TypedElement<G>>, Deps)> {
+        match self.peek(n)? {
+            StackElement::Element(el, deps) => Ok((el.clone(), deps.clone())),
+            el => Err(OperationError::ExpectedElement {
+                position: self.stack.len() + 1 - n,
+                found: el.kind(),
+            }),
+        }
+    }
+
+    fn peek_syn(&self, n: usize) -> Result<()> {
+        match self.peek(n)? {
+            StackElement::Synthetic => Ok(()),
+            el => Err(OperationError::ExpectedSyntheticMarker {
+                position: self.stack.len() + 1 - n,
+                found: el.kind(),
+            }),
+        }
+    }
+
+    /// Pop `n` entries that have all been validated
+    fn drop_top(&mut self, n: usize) {
+        for _ in 0..n {
+            match self.stack.last().unwrap() {
+                StackElement::Synthetic => self.syn_cnt -= 1,
+                StackElement::Argument => {
+                    self.arg_stack.drop_last_mut();
+                }
+                _ => (),
+            }
+            self.stack.drop_last_mut();
+        }
+    }
+
+    /// Add a symbol for a canonical element
+    fn add_sym(
+        &mut self,
+        s: String,
+        kind: SymbolKind,
+        el: Rc<TypedElement<G>>,
+        deps: Deps,
+    ) -> Result<()> {
+        self.expect_new_sym(&s)?;
+        debug_assert!(el.canonical);
+        self.sym_table.insert_mut(s, Symbol { kind, el, deps });
+        Ok(())
     }
 
-    fn add_sym(&mut self, s: String, is_real: bool, el: Rc<TypedElement<G>>) -> Result<()> {
-        if let Some(_) = self.sym_table.insert(s, (is_real, el)) {
-            return Err(OperationError::new("Symbol already exists"));
+    fn expect_new_sym(&self, s: &str) -> Result<()> {
+        if self.sym_table.contains_key(s) {
+            return Err(OperationError::SymbolExists { name: s.into() });
         }
         Ok(())
     }
@@ -462,78 +886,86 @@ impl<G: IdGenerator> Verifier<G> {
             .rev()
             .map(|x| TypedElement::new_argument(x.try_into().unwrap(), self.ty_reg.symbol()))
             .collect();
-        let mut el = Rc::new(new_object(&mut self.ty_reg, id, arr));
+        let mut el = Rc::new(new_object(&mut self.ty_reg, id.clone(), arr));
         for _ in 0..n {
             el = self.new_universal(el);
         }
-        self.add_sym(s, false, el)?;
+        let el = self.normalize(&el, &mut self.fuel("obj"))?;
+        self.add_sym(s.clone(), SymbolKind::Object, el, Deps::new())?;
+        self.obj_names.insert_mut(id, s);
         Ok(())
     }
 
-    fn pop_element(&mut self) -> Result<Rc<TypedElement<G>>> {
-        if let StackElement::Element(el) = self.pop()? {
-            Ok(el)
-        } else {
-            Err(OperationError::new("Expected element on stack top"))
+    fn split_imply(
+        &mut self,
+        mut el: Rc<TypedElement<G>>,
+        fuel: &mut Fuel,
+    ) -> Result<(ElementPtr<G>, ElementPtr<G>)> {
+        if let Element::Object { id, args } = el.unwrap_one(&mut self.ty_reg, fuel)? {
+            if Some(id) == self.imply_id {
+                assert!(args.len() == 2);
+                return Ok((args[0].clone(), args[1].clone()));
+            }
         }
+        let found = self.show_top(&el);
+        Err(OperationError::NotImplication { found })
     }
 
-    fn pop_syn(&mut self) -> Result<()> {
-        if let StackElement::Synthetic = self.pop()? {
-            self.syn_cnt -= 1;
-            Ok(())
-        } else {
-            return Err(OperationError::new(
-                "Exporting an element in non-synthetic mode",
-            ));
-        }
-    }
-
-    fn pop_imply(&mut self) -> Result<(Rc<TypedElement<G>>, Rc<TypedElement<G>>)> {
-        let mut el = self.pop_element()?;
-        let data = el.unwrap_one(&mut self.ty_reg);
-        if let Element::Object { id, args: params } = data.deref() {
-            if id != &self.imply_id {
-                return Err(OperationError::new("Object is not imply"));
-            }
-            assert!(params.len() == 2);
-            Ok(match &params[..] {
-                [a, b] => (a.clone(), b.clone()),
-                _ => unreachable!(),
-            })
-        } else {
-            Err(OperationError::new("Not imply object"))
-        }
+    /// Render an element on the stack top, for error messages
+    fn show_top(&mut self, el: &Rc<TypedElement<G>>) -> String {
+        let depth = self.arg_stack.len();
+        self.printer().show(el, depth)
     }
 
     fn expect_syn(&mut self) -> Result<()> {
         if self.syn_cnt == 0 {
-            return Err(OperationError::new("Expected synthetic mode"));
+            return Err(OperationError::ExpectedSyntheticMode);
         }
         Ok(())
     }
 
     fn expect_real(&mut self) -> Result<()> {
         if self.syn_cnt > 0 {
-            return Err(OperationError::new("Expected non-synthetic mode"));
+            return Err(OperationError::ExpectedRealMode {
+                syn_depth: self.syn_cnt,
+            });
         }
         Ok(())
     }
 
     fn peek_types(&mut self) -> Result<(&mut Vec<ty::Type>, &mut ty::Registry)> {
-        match self.stack.last_mut() {
+        let depth = self.stack.len();
+        match depth.checked_sub(1).and_then(|i| self.stack.get_mut(i)) {
             Some(StackElement::Types(vec)) => Ok((vec, &mut self.ty_reg)),
-            _ => Err(OperationError::new("Expected types on stack top")),
+            el => Err(OperationError::ExpectedTypes {
+                depth,
+                found: el.map(|x| x.kind()),
+            }),
         }
     }
 
     fn new_universal(&mut self, body: Rc<TypedElement<G>>) -> Rc<TypedElement<G>> {
         let sym = self.ty_reg.symbol();
         let ty = self.ty_reg.infer(sym, body.ty.clone());
-        Rc::new(TypedElement::new_primitive(
-            Element::Universal { body: body },
-            ty,
-        ))
+        Rc::new(TypedElement::new_primitive(Element::Universal { body }, ty))
+    }
+}
+
+impl<G: IdGenerator> Recover for Verifier<G> {
+    type Snapshot = Checkpoint<G>;
+
+    fn snapshot(&self) -> Checkpoint<G> {
+        self.checkpoint()
+    }
+
+    fn rewind(&mut self, snapshot: &Checkpoint<G>) -> Result<()> {
+        self.restore(snapshot)
+    }
+}
+
+impl<G: IdGenerator + Default> Default for Verifier<G> {
+    fn default() -> Self {
+        Self::new(G::default())
     }
 }
 
@@ -545,130 +977,235 @@ fn new_object<G: IdGenerator>(
     TypedElement::new_primitive(Element::Object { id, args: params }, ty_reg.symbol())
 }
 
-impl<G: IdGenerator> super::isa::InstructionSet for Verifier<G> {
+impl<G: IdGenerator> InstructionSet for Verifier<G> {
     fn syn(&mut self) -> Result<()> {
+        self.check_time("syn")?;
+        self.expect_room(1, "syn")?;
         self.syn_cnt += 1;
-        self.stack.push(StackElement::Synthetic);
+        self.push(StackElement::Synthetic);
         Ok(())
     }
 
     fn app(&mut self) -> Result<()> {
-        let x = self.pop_element()?;
-        self.pop_syn()?;
-        let f = self.pop_element()?;
-        self.push(StackElement::Element(Rc::new(f.new_bind(x)?)));
+        self.check_time("app")?;
+        let (x, x_deps) = self.peek_element(1)?;
+        self.peek_syn(2)?;
+        let (f, f_deps) = self.peek_element(3)?;
+        let el = f.new_bind(x)?;
+        let x_deps = x_deps.iter().filter_map(Dep::as_term).collect();
+        self.drop_top(3);
+        self.push(StackElement::Element(
+            Rc::new(el),
+            set_union(&f_deps, &x_deps),
+        ));
         Ok(())
     }
 
     fn arg(&mut self, n: NonZeroUsize) -> Result<()> {
+        self.check_time("arg")?;
+        self.expect_room(1, "arg")?;
         self.expect_syn()?;
-        self.push(StackElement::Element(TypedElement::new_argument(
-            n,
-            vec_rev_get(&self.arg_stack, n.get())
-                .ok_or_else(|| {
-                    OperationError::new(format!("Argument index out of range: {}", n.get()))
-                })?
-                .clone(),
-        )));
+        let ty = vec_rev_get(&self.arg_stack, n.get())
+            .ok_or(OperationError::ArgumentOutOfRange {
+                index: n,
+                depth: self.arg_stack.len(),
+            })?
+            .clone();
+        self.push(StackElement::Element(
+            TypedElement::new_argument(n, ty),
+            Deps::new(),
+        ));
         Ok(())
     }
 
     fn uni(&mut self) -> Result<()> {
+        self.check_time("uni")?;
+        self.expect_room(1, "uni")?;
         self.push(StackElement::Types(Vec::new()));
         Ok(())
     }
 
     fn def(&mut self, s: String) -> Result<()> {
+        self.check_time("def")?;
         self.expect_real()?;
-        let el = self.pop_element()?;
+        let (el, deps) = self.peek_element(1)?;
         if el.max_ref != 0 {
-            return Err(OperationError::new("Exporting an unbounded element"));
+            return Err(OperationError::UnboundedExport {
+                name: s,
+                max_ref: el.max_ref,
+            });
         }
-        self.add_sym(s, true, el)?;
+        self.expect_new_sym(&s)?;
+        let el = self.normalize(&el, &mut self.fuel("def"))?;
+        self.add_sym(s, SymbolKind::Definition, el, deps)?;
+        self.drop_top(1);
         Ok(())
     }
 
     fn hyp(&mut self, s: String) -> Result<()> {
-        let el = self.pop_element()?;
-        self.pop_syn()?;
+        self.check_time("hyp")?;
+        let (el, deps) = self.peek_element(1)?;
+        self.peek_syn(2)?;
         if el.max_ref != 0 {
-            return Err(OperationError::new("Exporting an unbounded element"));
+            return Err(OperationError::UnboundedExport {
+                name: s,
+                max_ref: el.max_ref,
+            });
         }
-        self.add_sym(s, false, el)?;
+        self.expect_new_sym(&s)?;
+        let el = self.normalize(&el, &mut self.fuel("hyp"))?;
+        self.add_sym(s, SymbolKind::Hypothesis, el, deps)?;
+        self.drop_top(2);
         Ok(())
     }
 
     fn obj(&mut self, n: usize, s: String) -> Result<()> {
+        self.check_time("obj")?;
+        self.expect_new_sym(&s)?;
         let id = self.obj_id.new();
         self.add_obj(n, s, id)
     }
 
     fn hkt(&mut self) -> Result<()> {
+        self.check_time("hkt")?;
         let (vec, reg) = self.peek_types()?;
-        let q = s_pop(vec)?;
-        let p = s_pop(vec)?;
+        if vec.len() < 2 {
+            return Err(OperationError::TooFewTypes { found: vec.len() });
+        }
+        let q = vec.pop().unwrap();
+        let p = vec.pop().unwrap();
         vec.push(reg.infer(p, q));
         Ok(())
     }
 
     fn qed(&mut self) -> Result<()> {
-        match self.pop()? {
-            StackElement::Argument | StackElement::Synthetic => {
-                return Err(OperationError::new("Calling qed without uni"))
+        self.check_time("qed")?;
+        match self.peek(1)? {
+            el @ (StackElement::Argument | StackElement::Synthetic) => {
+                return Err(OperationError::QedWithoutUni { found: el.kind() })
             }
-            StackElement::Types(vec) => {
+            StackElement::Types(_) => {
+                let Some(StackElement::Types(vec)) = self.stack.last().cloned() else {
+                    unreachable!()
+                };
+                // the types are replaced by their arguments
+                self.expect_room(vec.len().saturating_sub(1), "qed")?;
+                self.stack.drop_last_mut();
                 for ty in vec.into_iter() {
-                    self.arg_stack.push(ty);
-                    self.stack.push(StackElement::Argument);
+                    self.arg_stack.push_back_mut(ty);
+                    self.push(StackElement::Argument);
                 }
             }
-            StackElement::Element(el) => {
-                if let StackElement::Argument = self.pop()? {
-                    self.arg_stack.pop().unwrap();
-                } else {
-                    return Err(OperationError::new("End of proof without an argument"));
+            StackElement::Element(el, deps) => {
+                let (el, deps) = (el.clone(), deps.clone());
+                match self.peek(2)? {
+                    StackElement::Argument => (),
+                    el => return Err(OperationError::QedWithoutArgument { found: el.kind() }),
                 };
-                let el = StackElement::Element(self.new_universal(el));
-                self.stack.push(el);
+                self.drop_top(2);
+                let el = StackElement::Element(self.new_universal(el), deps);
+                self.push(el);
             }
         }
         Ok(())
     }
 
     fn req(&mut self, s: &str) -> Result<()> {
-        let (is_real, el) = self
+        self.check_time("req")?;
+        self.expect_room(1, "req")?;
+        let sym = self
             .sym_table
             .get(s)
-            .ok_or_else(|| OperationError::new(format!("Symbol not found: {}", s)))?;
-        if !is_real && self.syn_cnt == 0 {
-            return Err(OperationError::new(format!(
-                "Using imaginary symbol {} in imaginary mode",
-                s
-            )));
-        }
-        self.push(StackElement::Element(el.clone()));
+            .ok_or_else(|| OperationError::SymbolNotFound { name: s.into() })?;
+        if !sym.kind.is_real() && self.syn_cnt == 0 {
+            return Err(OperationError::ImaginarySymbol { name: s.into() });
+        }
+        let el = StackElement::Element(sym.el.clone(), Deps::new().insert(Dep::Symbol(s.into())));
+        self.push(el);
         Ok(())
     }
 
     fn mp(&mut self) -> Result<()> {
+        self.check_time("mp")?;
         self.expect_syn()?;
-        let mut p = self.pop_element()?;
-        let (mut p_ans, q) = self.pop_imply()?;
-        if !TypedElement::check_equal(&mut p_ans, &mut p, &mut self.ty_reg) {
-            return Err(OperationError::new("Using mp but condition not met"));
+        let (p, p_deps) = self.peek_element(1)?;
+        let (imp, imp_deps) = self.peek_element(2)?;
+        let mut fuel = self.fuel("mp");
+        let (p_ans, q) = self.split_imply(imp, &mut fuel)?;
+        // Compared lazily rather than flushed: a term sharing its parts can be
+        // exponentially larger than its graph
+        let equal =
+            TypedElement::check_equal(&p_ans, &p, &mut self.ty_reg, &mut self.eq_memo, &mut fuel)?;
+        if !equal {
+            return Err(OperationError::ConditionNotMet {
+                expected: self.show_top(&p_ans),
+                actual: self.show_top(&p),
+            });
         }
-        self.push(StackElement::Element(q));
+        self.drop_top(2);
+        self.push(StackElement::Element(q, set_union(&imp_deps, &p_deps)));
         Ok(())
     }
 
     fn sat(&mut self) -> Result<()> {
+        self.check_time("sat")?;
         self.expect_real()?;
-        let (_, q) = self.pop_imply()?;
-        self.push(StackElement::Element(q));
+        let (imp, deps) = self.peek_element(1)?;
+        let (_, q) = self.split_imply(imp, &mut self.fuel("sat"))?;
+        self.drop_top(1);
+        self.push(StackElement::Element(q, deps.insert(Dep::Sat)));
+        Ok(())
+    }
+
+    fn dup(&mut self) -> Result<()> {
+        self.check_time("dup")?;
+        self.expect_room(1, "dup")?;
+        self.pick(NonZeroUsize::MIN)
+    }
+
+    fn pick(&mut self, n: NonZeroUsize) -> Result<()> {
+        self.check_time("pick")?;
+        self.expect_room(1, "pick")?;
+        let (el, deps) = self.peek_element(n.get())?;
+        let mut shift = 0;
+        for i in 1..n.get() {
+            match self.peek(i)? {
+                StackElement::Argument => shift += 1,
+                StackElement::Element(..) => (),
+                el => {
+                    return Err(OperationError::CrossingMarker {
+                        position: self.stack.len() + 1 - i,
+                        found: el.kind(),
+                    })
+                }
+            }
+        }
+        // the element is now under `shift` more binders
+        let el = el.set_shift(shift).map_or(el, Rc::new);
+        self.push(StackElement::Element(el, deps));
+        Ok(())
+    }
+
+    fn swap(&mut self) -> Result<()> {
+        self.check_time("swap")?;
+        self.peek_element(1)?;
+        self.peek_element(2)?;
+        let len = self.stack.len();
+        let (a, b) = (self.stack[len - 1].clone(), self.stack[len - 2].clone());
+        self.stack.set_mut(len - 1, b);
+        self.stack.set_mut(len - 2, a);
+        Ok(())
+    }
+
+    fn drop(&mut self) -> Result<()> {
+        self.check_time("drop")?;
+        self.peek_element(1)?;
+        self.drop_top(1);
         Ok(())
     }
 
     fn var(&mut self) -> Result<()> {
+        self.check_time("var")?;
         let (vec, reg) = self.peek_types()?;
         vec.push(reg.symbol());
         Ok(())
@@ -677,4 +1214,281 @@ impl<G: IdGenerator> super::isa::InstructionSet for Verifier<G> {
     fn has(&self, s: &str) -> bool {
         self.sym_table.contains_key(s)
     }
+
+    fn depth(&self) -> usize {
+        self.stack.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asm, util::CountGenerator};
+
+    /// The element that `src` leaves on top of the stack, in synthetic mode
+    fn term(vm: &mut Verifier, src: &str) -> Rc<TypedElement<CountGenerator>> {
+        asm::run(vm, src).unwrap();
+        let (el, _) = vm.peek_element(1).unwrap();
+        vm.drop_top(2);
+        el
+    }
+
+    fn equal(vm: &mut Verifier, a: &str, b: &str) -> bool {
+        let a = term(vm, a);
+        let b = term(vm, b);
+        let mut fuel = Fuel::unlimited();
+        let mut memo = memo::EqMemo::new();
+        TypedElement::check_equal(&a, &b, &mut vm.ty_reg, &mut memo, &mut fuel).unwrap()
+    }
+
+    #[test]
+    fn test_imply_arity() {
+        let mut vm: Verifier = Verifier::default();
+        vm.obj(0, "p".into()).unwrap();
+        vm.syn().unwrap();
+        vm.req("sys::imply").unwrap();
+        for _ in 0..2 {
+            vm.syn().unwrap();
+            vm.req("p").unwrap();
+            vm.app().unwrap();
+        }
+        vm.syn().unwrap();
+        vm.req("p").unwrap();
+        assert!(vm.app().is_err());
+    }
+
+    #[test]
+    fn test_flush() {
+        let mut vm: Verifier = Verifier::default();
+        let swap = "uni var qed uni var qed req f syn arg 1 app syn arg 2 app qed qed";
+        asm::run(&mut vm, "obj 2 f obj 0 p").unwrap();
+        // substitution under a binder
+        assert!(equal(
+            &mut vm,
+            &format!("syn {} syn req p app", swap),
+            "syn uni var qed req f syn arg 1 app syn req p app qed",
+        ));
+        // a free variable of the argument is shifted under the binder
+        assert!(equal(
+            &mut vm,
+            &format!("syn uni var qed {} syn arg 1 app qed", swap),
+            &format!("syn {}", swap),
+        ));
+        // both arguments, the first bound outside of the redex
+        assert!(equal(
+            &mut vm,
+            &format!("syn uni var qed {} syn arg 1 app syn req p app qed", swap),
+            "syn uni var qed req f syn req p app syn arg 1 app qed",
+        ));
+        // different terms stay apart
+        assert!(!equal(
+            &mut vm,
+            &format!("syn {} syn req p app", swap),
+            "syn uni var qed req f syn req p app syn arg 1 app qed",
+        ));
+    }
+
+    #[test]
+    fn test_equal_shared() {
+        let mut vm: Verifier = Verifier::default();
+        asm::run(&mut vm, "obj 1 f obj 0 p obj 0 q").unwrap();
+        asm::run(&mut vm, "syn req f syn req p app hyp a").unwrap();
+        // flushing `b` reads `a` while `a` is unwrapped
+        let a = term(&mut vm, "syn req a");
+        let b = term(&mut vm, "syn uni var qed req a qed syn req q app");
+        let mut fuel = Fuel::unlimited();
+        let mut memo = memo::EqMemo::new();
+        assert!(TypedElement::check_equal(&a, &b, &mut vm.ty_reg, &mut memo, &mut fuel).unwrap());
+        assert!(matches!(
+            a.data.borrow().deref(),
+            CacheEnum::Primitive(Element::Object { .. })
+        ));
+    }
+
+    #[test]
+    fn test_equal_unflushed() {
+        let mut vm: Verifier = Verifier::default();
+        asm::run(&mut vm, "obj 0 p").unwrap();
+        // the body refers to its binder only until it is flushed
+        assert!(equal(
+            &mut vm,
+            "syn uni var qed uni var qed req p qed syn arg 1 app qed",
+            "syn uni var qed req p qed",
+        ));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut vm: Verifier = Verifier::default();
+        match vm.req("sys::nope") {
+            Err(OperationError::SymbolNotFound { name }) => assert_eq!(name, "sys::nope"),
+            _ => panic!(),
+        }
+        match vm.req("sys::imply") {
+            Err(OperationError::ImaginarySymbol { name }) => assert_eq!(name, "sys::imply"),
+            _ => panic!(),
+        }
+        asm::run(&mut vm, "syn uni var qed").unwrap();
+        match vm.arg(NonZeroUsize::new(2).unwrap()) {
+            Err(OperationError::ArgumentOutOfRange { index, depth }) => {
+                assert_eq!((index.get(), depth), (2, 1))
+            }
+            _ => panic!(),
+        }
+        match vm.qed() {
+            Err(OperationError::QedWithoutUni { found }) => assert_eq!(found, StackKind::Argument),
+            _ => panic!(),
+        }
+        asm::run(&mut vm, "uni var").unwrap();
+        match vm.hkt() {
+            Err(OperationError::TooFewTypes { found }) => assert_eq!(found, 1),
+            _ => panic!(),
+        }
+    }
+
+    /// Everything an instruction is allowed to change
+    fn snapshot(vm: &Verifier) -> (usize, Vec<ty::Type>, Vec<String>, Vec<String>) {
+        let stack = vm
+            .stack
+            .iter()
+            .map(|x| match x {
+                StackElement::Element(el, _) => format!("{:p}", Rc::as_ptr(el)),
+                StackElement::Types(vec) => format!("{:?}", vec),
+                el => el.kind().to_string(),
+            })
+            .collect();
+        let mut syms: Vec<_> = vm
+            .sym_table
+            .iter()
+            .map(|(k, sym)| format!("{} {:?} {:p}", k, sym.kind, Rc::as_ptr(&sym.el)))
+            .collect();
+        syms.sort();
+        (vm.syn_cnt, vm.arg_stack.iter().cloned().collect(), stack, syms)
+    }
+
+    #[test]
+    fn test_atomic() {
+        let mut vm: Verifier = Verifier::default();
+        let cases = [
+            // type mismatch after popping the argument
+            ("syn uni var var hkt var hkt var qed arg 2 syn arg 1", "app"),
+            // condition not met after popping both operands
+            ("syn uni var var qed req sys::l1 syn arg 1 app syn arg 2 app arg 2", "mp"),
+            ("syn uni var var qed req sys::l1 syn arg 1 app syn arg 2 app", "mp"),
+            ("syn uni var qed syn arg 1", "hyp sys::l1"),
+            ("syn uni var qed arg 1", "hyp x"),
+            ("syn uni var qed req sys::not syn arg 1 app req sys::not", "qed"),
+            ("uni var", "hkt"),
+            ("", "obj 2 sys::imply"),
+            ("req sys::l1", "def sys::l2"),
+            ("req sys::l1 syn req sys::not", "sat"),
+            ("req sys::l1 syn req sys::not", "pick 3"),
+            ("uni var req sys::l1", "pick 2"),
+            ("req sys::l1 syn", "swap"),
+            ("syn", "drop"),
+        ];
+        for (setup, ins) in cases {
+            let mut vm: Verifier = Verifier::default();
+            asm::run(&mut vm, setup).unwrap();
+            let before = snapshot(&vm);
+            assert!(asm::run(&mut vm, ins).is_err(), "{} {}", setup, ins);
+            assert_eq!(snapshot(&vm), before, "{} {}", setup, ins);
+        }
+        // the session can continue after an error
+        asm::run(&mut vm, "syn uni var qed req sys::not syn arg 1 app").unwrap();
+        assert!(vm.app().is_err());
+        asm::run(&mut vm, "qed hyp not_a").unwrap();
+        assert!(vm.has("not_a"));
+    }
+
+    #[test]
+    fn test_stack() {
+        let mut vm: Verifier = Verifier::default();
+        asm::run(&mut vm, "req sys::l1 dup req sys::l2 swap").unwrap();
+        assert_eq!(vm.stack.len(), 3);
+        match &vm.stack.iter().collect::<Vec<_>>()[..] {
+            [StackElement::Element(a, _), StackElement::Element(b, _), StackElement::Element(c, _)] => {
+                assert!(Rc::ptr_eq(a, c) && !Rc::ptr_eq(a, b))
+            }
+            _ => panic!(),
+        }
+        asm::run(&mut vm, "drop drop drop").unwrap();
+        assert!(vm.stack.is_empty());
+
+        // picking below an argument shifts the element into the binder
+        asm::run(&mut vm, "syn uni var qed arg 1 uni var qed pick 2 qed").unwrap();
+        assert_eq!(vm.show_stack()[2..], ["a : @", "∀b. a : @=>@"]);
+        match vm.pick(NonZeroUsize::new(3).unwrap()) {
+            Err(err @ OperationError::ExpectedElement { .. }) => assert_eq!(
+                err.to_string(),
+                "Expected element, found argument at position 2 from the bottom"
+            ),
+            _ => panic!(),
+        }
+        vm.syn().unwrap();
+        match vm.pick(NonZeroUsize::new(2).unwrap()) {
+            Err(OperationError::CrossingMarker { position, found }) => {
+                assert_eq!((position, found), (5, StackKind::Synthetic))
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_flush_type_error() {
+        // a bind that skipped the check of `new_bind`, as in a malformed image
+        let mut vm: Verifier = Verifier::default();
+        let sym = vm.ty_reg.symbol();
+        let var = TypedElement::<CountGenerator>::new_argument(NonZeroUsize::MIN, sym.clone());
+        let mut el = Rc::new(TypedElement {
+            data: RefCell::new(CacheEnum::Bind {
+                func: var.clone(),
+                arg: var,
+            }),
+            max_ref: 1,
+            ty: sym,
+            canonical: false,
+            size: 0,
+        });
+        assert!(matches!(
+            el.unwrap_one(&mut vm.ty_reg, &mut Fuel::unlimited()),
+            Err(OperationError::CannotApply { .. })
+        ));
+    }
+
+    #[test]
+    fn test_deep() {
+        // `p -> (p -> ... p)`, far deeper than recursion on the native stack
+        // could handle
+        const DEPTH: usize = 200_000;
+        let mut vm: Verifier = Verifier::default();
+        asm::run(&mut vm, "obj 0 p syn").unwrap();
+        for _ in 0..DEPTH {
+            vm.req("sys::imply").unwrap();
+            vm.syn().unwrap();
+            vm.req("p").unwrap();
+            vm.app().unwrap();
+            vm.syn().unwrap();
+        }
+        vm.req("p").unwrap();
+        for _ in 0..DEPTH {
+            vm.app().unwrap();
+        }
+        vm.hyp("deep".into()).unwrap();
+        asm::run(
+            &mut vm,
+            "
+            req sys::l1 syn req deep app syn req p app def l1_deep
+            syn req l1_deep req deep mp hyp p_deep
+            ",
+        )
+        .unwrap();
+        let shown = vm.show("p_deep").unwrap();
+        assert_eq!(shown.len(), "sys::imply(p, )".len() * (DEPTH + 1) + 1);
+        asm::run(&mut vm, "syn req l1_deep req p").unwrap();
+        assert!(matches!(
+            vm.mp(),
+            Err(OperationError::ConditionNotMet { .. })
+        ));
+    }
 }
@@ -0,0 +1,188 @@
+//! What a symbol ultimately rests on.
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt,
+};
+
+use super::{Dep, Verifier};
+use crate::{
+    err::{OperationError, Result},
+    util::IdGenerator,
+};
+
+/// How a symbol entered the table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SymbolKind {
+    /// `obj`
+    Object,
+    /// Installed by the kernel
+    Axiom,
+    /// `hyp`
+    Hypothesis,
+    /// `def`
+    Definition,
+}
+
+impl SymbolKind {
+    /// Whether the symbol can be required outside of synthetic mode
+    pub fn is_real(self) -> bool {
+        matches!(self, SymbolKind::Axiom | SymbolKind::Definition)
+    }
+}
+
+/// Something taken for granted, reachable from a symbol
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Assumption {
+    Axiom(String),
+    Hypothesis(String),
+    Object(String),
+    /// `sat` was used to build the named symbol
+    Sat(String),
+}
+
+impl fmt::Display for Assumption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Assumption::Axiom(s) => write!(f, "axiom {}", s),
+            Assumption::Hypothesis(s) => write!(f, "hyp {}", s),
+            Assumption::Object(s) => write!(f, "obj {}", s),
+            Assumption::Sat(s) => write!(f, "sat in {}", s),
+        }
+    }
+}
+
+impl<G: IdGenerator> Verifier<G> {
+    pub fn kind(&self, name: &str) -> Option<SymbolKind> {
+        self.sym_table.get(name).map(|x| x.kind)
+    }
+
+    /// The axioms, hypotheses and `sat` uses that the proof of `name`
+    /// transitively depends on, and the objects it mentions, including `name`
+    /// itself unless it is a definition.
+    ///
+    /// A symbol that is only passed as an argument to `app` contributes its
+    /// statement and not its proof, so only its objects are counted.
+    pub fn assumptions(&self, name: &str) -> Result<BTreeSet<Assumption>> {
+        if !self.sym_table.contains_key(name) {
+            return Err(OperationError::SymbolNotFound { name: name.into() });
+        }
+        let mut ans = BTreeSet::new();
+        let mut seen = HashSet::new();
+        // with whether the symbol is part of the proof
+        let mut todo = vec![(name, true)];
+        while let Some((s, proof)) = todo.pop() {
+            if !seen.insert((s, proof)) {
+                continue;
+            }
+            let sym = &self.sym_table[s];
+            match sym.kind {
+                SymbolKind::Object => ans.insert(Assumption::Object(s.into())),
+                SymbolKind::Axiom if proof => ans.insert(Assumption::Axiom(s.into())),
+                SymbolKind::Hypothesis if proof => ans.insert(Assumption::Hypothesis(s.into())),
+                _ => false,
+            };
+            for dep in sym.deps.iter() {
+                match dep {
+                    Dep::Symbol(x) => todo.push((x, proof)),
+                    Dep::Term(x) => todo.push((x, false)),
+                    Dep::Sat if proof => {
+                        ans.insert(Assumption::Sat(s.into()));
+                    }
+                    Dep::Sat => (),
+                }
+            }
+        }
+        Ok(ans)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asm, vm::Verifier};
+
+    #[test]
+    fn test_assumptions() {
+        let mut vm: Verifier = Verifier::default();
+        asm::run(
+            &mut vm,
+            "
+            obj 0 p
+            syn req sys::imply syn req p app syn req p app hyp p_p
+            req sys::l1 syn req p app syn req p app def t1
+            req sys::l1 syn req p_p app syn req p app sat def t2
+            syn req p_p req p mp hyp p2
+            ",
+        )
+        .unwrap();
+        let names = |v: &[&str]| -> BTreeSet<Assumption> {
+            v.iter()
+                .map(|s| {
+                    let (kind, name) = s.split_once(' ').unwrap();
+                    let name = name.to_string();
+                    match kind {
+                        "axiom" => Assumption::Axiom(name),
+                        "hyp" => Assumption::Hypothesis(name),
+                        "obj" => Assumption::Object(name),
+                        _ => Assumption::Sat(name),
+                    }
+                })
+                .collect()
+        };
+        let base = ["axiom sys::l1", "obj sys::imply", "obj p"];
+        assert_eq!(vm.assumptions("t1").unwrap(), names(&base));
+        // `p_p` is only the statement that `sat` assumes
+        let mut t2 = base.to_vec();
+        t2.push("sat t2");
+        assert_eq!(vm.assumptions("t2").unwrap(), names(&t2));
+        // and here it is a premise of `mp`
+        assert_eq!(
+            vm.assumptions("p2").unwrap(),
+            names(&["obj sys::imply", "obj p", "hyp p_p", "hyp p2"])
+        );
+        assert_eq!(vm.kind("t2"), Some(SymbolKind::Definition));
+        assert!(vm.assumptions("t3").is_err());
+    }
+
+    #[test]
+    fn test_certify() {
+        let mut vm: Verifier = Verifier::default();
+        // `a -> a` by `mp` from the axioms, then used by `mp` again
+        asm::run(
+            &mut vm,
+            "
+            syn
+            uni var qed
+              req sys::l2
+                syn arg 1 app
+                syn req sys::imply syn arg 1 app syn arg 1 app app
+                syn arg 1 app
+              req sys::l1
+                syn arg 1 app
+                syn req sys::imply syn arg 1 app syn arg 1 app app
+              mp
+              req sys::l1 syn arg 1 app syn arg 1 app
+              mp
+            qed
+            hyp refl
+            obj 0 p
+            syn
+            req sys::l1 syn req sys::imply syn req p app syn req p app app syn req p app
+            req refl syn req p app
+            mp hyp weak
+            ",
+        )
+        .unwrap();
+        let mut expected = BTreeSet::from([
+            Assumption::Axiom("sys::l1".into()),
+            Assumption::Axiom("sys::l2".into()),
+            Assumption::Object("sys::imply".into()),
+            Assumption::Hypothesis("refl".into()),
+        ]);
+        assert_eq!(vm.assumptions("refl").unwrap(), expected);
+        expected.insert(Assumption::Object("p".into()));
+        expected.insert(Assumption::Hypothesis("weak".into()));
+        assert_eq!(vm.assumptions("weak").unwrap(), expected);
+    }
+}
@@ -0,0 +1,127 @@
+//! Saving and restoring the state for backtracking.
+//!
+//! The stack and the tables are persistent, so taking a checkpoint only
+//! copies a few pointers and any number of checkpoints share their common
+//! parts. Object ids are never handed out twice, even by a branch that is
+//! abandoned later. A checkpoint only restores into the verifier it was taken
+//! from, as object ids of different verifiers may collide.
+
+use super::{ty, ObjectNames, StackElement, SymbolTable, Verifier};
+use crate::{
+    err::{OperationError, Result},
+    util::{CountGenerator, IdGenerator},
+};
+
+use rpds::Vector;
+use std::rc::Rc;
+
+/// The state of a [`Verifier`] at some point, see [`Verifier::checkpoint`]
+pub struct Checkpoint<G: IdGenerator = CountGenerator> {
+    syn_cnt: usize,
+    arg_stack: Vector<ty::Type>,
+    stack: Vector<StackElement<G>>,
+    sym_table: SymbolTable<G>,
+    obj_names: ObjectNames<G>,
+    verifier: Rc<()>,
+}
+
+impl<G: IdGenerator> Clone for Checkpoint<G> {
+    fn clone(&self) -> Self {
+        Self {
+            syn_cnt: self.syn_cnt,
+            arg_stack: self.arg_stack.clone(),
+            stack: self.stack.clone(),
+            sym_table: self.sym_table.clone(),
+            obj_names: self.obj_names.clone(),
+            verifier: self.verifier.clone(),
+        }
+    }
+}
+
+impl<G: IdGenerator> Checkpoint<G> {
+    /// Depth of the stack when the checkpoint was taken
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn has(&self, name: &str) -> bool {
+        self.sym_table.contains_key(name)
+    }
+}
+
+impl<G: IdGenerator> Verifier<G> {
+    /// Take the current state in O(1)
+    pub fn checkpoint(&self) -> Checkpoint<G> {
+        Checkpoint {
+            syn_cnt: self.syn_cnt,
+            arg_stack: self.arg_stack.clone(),
+            stack: self.stack.clone(),
+            sym_table: self.sym_table.clone(),
+            obj_names: self.obj_names.clone(),
+            verifier: self.identity.clone(),
+        }
+    }
+
+    /// Go back to the state of `cp` in O(1), failing if it was taken from
+    /// another verifier. The checkpoint stays valid and can be restored again.
+    pub fn restore(&mut self, cp: &Checkpoint<G>) -> Result<()> {
+        if !Rc::ptr_eq(&cp.verifier, &self.identity) {
+            return Err(OperationError::ForeignCheckpoint);
+        }
+        let cp = cp.clone();
+        self.syn_cnt = cp.syn_cnt;
+        self.arg_stack = cp.arg_stack;
+        self.stack = cp.stack;
+        self.sym_table = cp.sym_table;
+        self.obj_names = cp.obj_names;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asm, isa::InstructionSet};
+
+    #[test]
+    fn test_branches() {
+        let mut vm: Verifier = Verifier::default();
+        asm::run(&mut vm, "syn uni var qed").unwrap();
+        let base = vm.checkpoint();
+
+        asm::run(&mut vm, "arg 1 qed hyp id obj 0 a").unwrap();
+        let left = vm.checkpoint();
+        vm.restore(&base).unwrap();
+        assert!(!vm.has("id") && !vm.has("a"));
+        assert_eq!((vm.stack.len(), vm.syn_depth()), (2, 1));
+
+        // a different symbol under the same name, and an object of its own
+        asm::run(&mut vm, "req sys::not syn arg 1 app qed hyp id obj 0 b").unwrap();
+        let right = vm.checkpoint();
+        assert!(vm.stack.is_empty() && !vm.has("a"));
+
+        vm.restore(&left).unwrap();
+        assert_eq!(vm.show("id").unwrap(), "∀a. a");
+        assert_eq!(vm.show("a").unwrap(), "a");
+        vm.restore(&right).unwrap();
+        assert_eq!(vm.show("id").unwrap(), "∀a. sys::not(a)");
+        assert_eq!(vm.show("b").unwrap(), "b");
+        assert!(left.has("a") && !right.has("a") && !base.has("id"));
+
+        // an error in a branch leaves the checkpoints alone
+        vm.restore(&base).unwrap();
+        assert!(asm::run(&mut vm, "arg 1 qed hyp sys::l1").is_err());
+        vm.restore(&base).unwrap();
+        asm::run(&mut vm, "arg 1 qed hyp id").unwrap();
+        assert_eq!(base.depth(), 2);
+
+        // same state, different id space
+        let mut other: Verifier = Verifier::default();
+        asm::run(&mut other, "syn uni var qed").unwrap();
+        assert!(matches!(
+            other.restore(&base),
+            Err(OperationError::ForeignCheckpoint)
+        ));
+        assert!(other.has("sys::l1") && other.stack.len() == 2);
+    }
+}
@@ -0,0 +1,760 @@
+//! Binary images of the symbol table.
+//!
+//! An image holds every symbol with its kind, its dependencies and the element
+//! DAG behind it, sharing included, so that it loads without checking any proof
+//! again.
+//! Objects are stored by name. On load they are bound to the objects of the
+//! same name in the verifier, or to fresh ids if there are none, so images
+//! built in separate sessions compose.
+//!
+//! Images are trusted input. The proofs of their definitions are not checked
+//! again, so a definition and the dependencies it reports are only as sound
+//! as the verifier that wrote the image. Loading still types every element,
+//! checks that every object is used with the arity of its symbol and refuses
+//! axioms the kernel of the verifier did not provide, so that a corrupt or
+//! mismatched image fails to load instead of panicking later.
+//!
+//! Integers are LEB128 and strings are length-prefixed UTF-8. After the magic
+//! and the version come the types, the object names, the elements and the
+//! symbols, each as a count followed by entries that only refer to earlier
+//! entries.
+
+use std::{
+    cell::RefCell,
+    cmp::max,
+    collections::HashMap,
+    error::Error,
+    fmt,
+    io::{self, Read, Write},
+    num::NonZeroUsize,
+    ops::Deref,
+    rc::Rc,
+};
+
+use super::{
+    limits::Limit, max_ref_shift, ty::Type, CacheEnum, Dep, Deps, Element, ObjectNames, Symbol,
+    SymbolKind, TypedElement, Verifier,
+};
+use crate::{err::OperationError, util::IdGenerator};
+
+const MAGIC: &[u8; 4] = b"AXLI";
+const VERSION: u8 = 3;
+
+const OBJECT: u8 = 0;
+const UNIVERSAL: u8 = 1;
+const VARIABLE: u8 = 2;
+const BIND: u8 = 3;
+const REF_SHIFT: u8 = 4;
+
+const KINDS: [SymbolKind; 4] = [
+    SymbolKind::Object,
+    SymbolKind::Axiom,
+    SymbolKind::Hypothesis,
+    SymbolKind::Definition,
+];
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(io::Error),
+    Malformed(&'static str),
+    /// The symbol exists in the verifier with another element or kind
+    Conflict {
+        name: String,
+    },
+    /// An axiom that the verifier does not have
+    UnknownAxiom {
+        name: String,
+    },
+    /// Loading went over the limits of the verifier
+    LimitExceeded(Limit),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageError::Io(e) => write!(f, "{}", e),
+            ImageError::Malformed(what) => write!(f, "Malformed image: {}", what),
+            ImageError::Conflict { name } => {
+                write!(f, "Symbol {} differs from the existing one", name)
+            }
+            ImageError::UnknownAxiom { name } => {
+                write!(f, "Axiom {} is not provided by the kernel", name)
+            }
+            ImageError::LimitExceeded(limit) => {
+                write!(f, "Limit exceeded while loading an image: {}", limit)
+            }
+        }
+    }
+}
+
+impl Error for ImageError {}
+
+impl From<io::Error> for ImageError {
+    fn from(e: io::Error) -> Self {
+        ImageError::Io(e)
+    }
+}
+
+/// Elements are typed on load, so only a limit can make walking them fail
+impl From<OperationError> for ImageError {
+    fn from(e: OperationError) -> Self {
+        match e {
+            OperationError::LimitExceeded { limit, .. } => ImageError::LimitExceeded(limit),
+            _ => ImageError::Malformed("ill-typed element"),
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, ImageError>;
+
+fn put_uint(buf: &mut Vec<u8>, mut x: usize) {
+    loop {
+        let byte = (x & 0x7f) as u8;
+        x >>= 7;
+        if x == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+fn put_str(buf: &mut Vec<u8>, s: &str) {
+    put_uint(buf, s.len());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+struct Encoder<'a, G: IdGenerator> {
+    obj_names: &'a ObjectNames<G>,
+    types: HashMap<Type, usize>,
+    type_buf: Vec<u8>,
+    objects: HashMap<G::Id, usize>,
+    object_buf: Vec<u8>,
+    nodes: HashMap<*const TypedElement<G>, usize>,
+    node_buf: Vec<u8>,
+}
+
+impl<'a, G: IdGenerator> Encoder<'a, G> {
+    fn ty(&mut self, ty: &Type) -> usize {
+        if let Some(&i) = self.types.get(ty) {
+            return i;
+        }
+        match ty.split() {
+            None => self.type_buf.push(0),
+            Some((p, q)) => {
+                let p = self.ty(&p);
+                let q = self.ty(&q);
+                self.type_buf.push(1);
+                put_uint(&mut self.type_buf, p);
+                put_uint(&mut self.type_buf, q);
+            }
+        }
+        let i = self.types.len();
+        self.types.insert(ty.clone(), i);
+        i
+    }
+
+    fn object(&mut self, id: &G::Id) -> usize {
+        if let Some(&i) = self.objects.get(id) {
+            return i;
+        }
+        let name = self
+            .obj_names
+            .get(id)
+            .expect("objects are created with a name");
+        put_str(&mut self.object_buf, name);
+        let i = self.objects.len();
+        self.objects.insert(id.clone(), i);
+        i
+    }
+
+    /// The index of `el`, after those of its parts in order
+    fn node(&mut self, el: &Rc<TypedElement<G>>) -> usize {
+        let mut todo = vec![el.clone()];
+        while let Some(el) = todo.last().cloned() {
+            if self.nodes.contains_key(&Rc::as_ptr(&el)) {
+                todo.pop();
+                continue;
+            }
+            let data = el.data.borrow().clone();
+            let parts = match &data {
+                CacheEnum::Primitive(Element::Object { id, args }) => {
+                    self.object(id);
+                    args.clone()
+                }
+                CacheEnum::Primitive(Element::Variable { args, .. }) => args.clone(),
+                CacheEnum::Primitive(Element::Universal { body }) => vec![body.clone()],
+                CacheEnum::Bind { func, arg } => vec![func.clone(), arg.clone()],
+                CacheEnum::RefShift(p, _) => vec![p.clone()],
+            };
+            let pending: Vec<_> = parts
+                .into_iter()
+                .filter(|x| !self.nodes.contains_key(&Rc::as_ptr(x)))
+                .collect();
+            if !pending.is_empty() {
+                todo.extend(pending.into_iter().rev());
+                continue;
+            }
+            todo.pop();
+            self.emit(&el, data);
+        }
+        self.nodes[&Rc::as_ptr(el)]
+    }
+
+    fn indices(&self, els: &[Rc<TypedElement<G>>]) -> Vec<usize> {
+        els.iter().map(|x| self.nodes[&Rc::as_ptr(x)]).collect()
+    }
+
+    /// Write a node whose parts are written
+    fn emit(&mut self, el: &Rc<TypedElement<G>>, data: CacheEnum<G, Rc<TypedElement<G>>>) {
+        let (tag, fields) = match data {
+            CacheEnum::Primitive(Element::Object { id, args }) => {
+                let mut fields = vec![self.object(&id), args.len()];
+                fields.extend(self.indices(&args));
+                (OBJECT, fields)
+            }
+            CacheEnum::Primitive(Element::Universal { body }) => (UNIVERSAL, self.indices(&[body])),
+            CacheEnum::Primitive(Element::Variable { pos, args }) => {
+                let mut fields = vec![pos.get(), args.len()];
+                fields.extend(self.indices(&args));
+                (VARIABLE, fields)
+            }
+            CacheEnum::Bind { func, arg } => (BIND, self.indices(&[func, arg])),
+            CacheEnum::RefShift(p, delta) => {
+                let mut fields = self.indices(&[p]);
+                fields.push(delta.get());
+                (REF_SHIFT, fields)
+            }
+        };
+        let ty = self.ty(&el.ty);
+        self.node_buf.push(tag);
+        for x in fields {
+            put_uint(&mut self.node_buf, x);
+        }
+        put_uint(&mut self.node_buf, ty);
+        let i = self.nodes.len();
+        self.nodes.insert(Rc::as_ptr(el), i);
+    }
+}
+
+struct Decoder<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn byte(&mut self) -> Result<u8> {
+        let (&x, rest) = self
+            .buf
+            .split_first()
+            .ok_or(ImageError::Malformed("unexpected end"))?;
+        self.buf = rest;
+        Ok(x)
+    }
+
+    fn uint(&mut self) -> Result<usize> {
+        let mut x = 0usize;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            let bits = (byte & 0x7f) as usize;
+            if shift >= usize::BITS || (bits << shift) >> shift != bits {
+                return Err(ImageError::Malformed("integer overflow"));
+            }
+            x |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(x);
+            }
+            shift += 7;
+        }
+    }
+
+    /// An index into a table of `len` entries
+    fn index(&mut self, len: usize) -> Result<usize> {
+        let i = self.uint()?;
+        if i >= len {
+            return Err(ImageError::Malformed("index out of range"));
+        }
+        Ok(i)
+    }
+
+    fn str(&mut self) -> Result<String> {
+        let len = self.uint()?;
+        if len > self.buf.len() {
+            return Err(ImageError::Malformed("unexpected end"));
+        }
+        let (s, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        String::from_utf8(s.to_vec()).map_err(|_| ImageError::Malformed("invalid string"))
+    }
+}
+
+impl<G: IdGenerator> Verifier<G> {
+    /// Write the symbol table as an image
+    pub fn save<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let mut enc = Encoder {
+            obj_names: &self.obj_names,
+            types: HashMap::new(),
+            type_buf: Vec::new(),
+            objects: HashMap::new(),
+            object_buf: Vec::new(),
+            nodes: HashMap::new(),
+            node_buf: Vec::new(),
+        };
+        let mut names: Vec<_> = self.sym_table.keys().collect();
+        names.sort();
+        let mut sym_buf = Vec::new();
+        put_uint(&mut sym_buf, names.len());
+        for name in names {
+            let sym = &self.sym_table[name];
+            let i = enc.node(&sym.el);
+            put_str(&mut sym_buf, name);
+            sym_buf.push(KINDS.iter().position(|&x| x == sym.kind).unwrap() as u8);
+            put_uint(&mut sym_buf, i);
+            put_uint(&mut sym_buf, sym.deps.size());
+            for dep in sym.deps.iter() {
+                match dep {
+                    Dep::Sat => sym_buf.push(0),
+                    Dep::Symbol(s) => {
+                        sym_buf.push(1);
+                        put_str(&mut sym_buf, s);
+                    }
+                    Dep::Term(s) => {
+                        sym_buf.push(2);
+                        put_str(&mut sym_buf, s);
+                    }
+                }
+            }
+        }
+        let mut buf = MAGIC.to_vec();
+        buf.push(VERSION);
+        put_uint(&mut buf, enc.types.len());
+        buf.extend(enc.type_buf);
+        put_uint(&mut buf, enc.objects.len());
+        buf.extend(enc.object_buf);
+        put_uint(&mut buf, enc.nodes.len());
+        buf.extend(enc.node_buf);
+        buf.extend(sym_buf);
+        w.write_all(&buf)
+    }
+
+    /// Add the symbols of an image without checking their proofs, which is
+    /// only sound for images written by a trusted verifier.
+    ///
+    /// Symbols that already exist must be equal to those in the image, and
+    /// are kept. Nothing is added if the image fails to load. Flushing and
+    /// comparing the elements counts against the limits of one instruction.
+    pub fn load<R: Read>(&mut self, mut r: R) -> Result<()> {
+        self.check_time("load")?;
+        let mut fuel = self.fuel("load");
+        let mut buf = Vec::new();
+        r.read_to_end(&mut buf)?;
+        let mut dec = Decoder { buf: &buf };
+        if dec.buf.len() < MAGIC.len() || &dec.buf[..MAGIC.len()] != MAGIC {
+            return Err(ImageError::Malformed("bad magic"));
+        }
+        dec.buf = &dec.buf[MAGIC.len()..];
+        if dec.byte()? != VERSION {
+            return Err(ImageError::Malformed("unsupported version"));
+        }
+
+        let mut types: Vec<Type> = Vec::new();
+        for _ in 0..dec.uint()? {
+            let ty = match dec.byte()? {
+                0 => self.ty_reg.symbol(),
+                1 => {
+                    let p = types[dec.index(types.len())?].clone();
+                    let q = types[dec.index(types.len())?].clone();
+                    self.ty_reg.infer(p, q)
+                }
+                _ => return Err(ImageError::Malformed("unknown type")),
+            };
+            types.push(ty);
+        }
+
+        let existing: HashMap<&str, &G::Id> = self
+            .obj_names
+            .iter()
+            .map(|(id, s)| (s.as_str(), id))
+            .collect();
+        let mut objects = Vec::new();
+        let mut new_objects = Vec::new();
+        for _ in 0..dec.uint()? {
+            let name = dec.str()?;
+            match existing.get(name.as_str()) {
+                Some(&id) => objects.push((id.clone(), name)),
+                None => {
+                    let id = self.obj_id.new();
+                    objects.push((id.clone(), name.clone()));
+                    new_objects.push((id, name));
+                }
+            }
+        }
+        // the number of arguments each object is used with
+        let mut arities = vec![None; objects.len()];
+
+        let mut nodes: Vec<Rc<TypedElement<G>>> = Vec::new();
+        for _ in 0..dec.uint()? {
+            let tag = dec.byte()?;
+            let args = |dec: &mut Decoder| -> Result<Vec<_>> {
+                (0..dec.uint()?)
+                    .map(|_| Ok(nodes[dec.index(nodes.len())?].clone()))
+                    .collect()
+            };
+            let data = match tag {
+                OBJECT => {
+                    let i = dec.index(objects.len())?;
+                    let args = args(&mut dec)?;
+                    if *arities[i].get_or_insert(args.len()) != args.len() {
+                        return Err(ImageError::Malformed("object used with another arity"));
+                    }
+                    CacheEnum::Primitive(Element::Object {
+                        id: objects[i].0.clone(),
+                        args,
+                    })
+                }
+                UNIVERSAL => {
+                    let body = nodes[dec.index(nodes.len())?].clone();
+                    CacheEnum::Primitive(Element::Universal { body })
+                }
+                VARIABLE => {
+                    let pos = NonZeroUsize::new(dec.uint()?)
+                        .ok_or(ImageError::Malformed("variable at 0"))?;
+                    CacheEnum::Primitive(Element::Variable {
+                        pos,
+                        args: args(&mut dec)?,
+                    })
+                }
+                BIND => {
+                    let func = nodes[dec.index(nodes.len())?].clone();
+                    let arg = nodes[dec.index(nodes.len())?].clone();
+                    CacheEnum::Bind { func, arg }
+                }
+                REF_SHIFT => {
+                    let p = nodes[dec.index(nodes.len())?].clone();
+                    let delta = NonZeroUsize::new(dec.uint()?)
+                        .ok_or(ImageError::Malformed("shift by 0"))?;
+                    CacheEnum::RefShift(p, delta)
+                }
+                _ => return Err(ImageError::Malformed("unknown element")),
+            };
+            let ty = types[dec.index(types.len())?].clone();
+            if !self.type_checks(&data, &ty) {
+                return Err(ImageError::Malformed("ill-typed element"));
+            }
+            let el = match data {
+                CacheEnum::Primitive(el) => TypedElement::new_primitive(el, ty),
+                CacheEnum::Bind { func, arg } => TypedElement {
+                    max_ref: max(func.max_ref, arg.max_ref),
+                    data: RefCell::new(CacheEnum::Bind { func, arg }),
+                    ty,
+                    canonical: false,
+                    size: 0,
+                },
+                CacheEnum::RefShift(p, delta) => TypedElement {
+                    max_ref: max_ref_shift(p.max_ref, delta.get()),
+                    data: RefCell::new(CacheEnum::RefShift(p, delta)),
+                    ty,
+                    canonical: false,
+                    size: 0,
+                },
+            };
+            nodes.push(Rc::new(el));
+        }
+
+        let mut syms = HashMap::new();
+        for _ in 0..dec.uint()? {
+            let name = dec.str()?;
+            let kind = *KINDS
+                .get(dec.byte()? as usize)
+                .ok_or(ImageError::Malformed("unknown symbol kind"))?;
+            let el = nodes[dec.index(nodes.len())?].clone();
+            if el.max_ref != 0 {
+                return Err(ImageError::Malformed("unbounded symbol"));
+            }
+            let el = self.normalize(&el, &mut fuel)?;
+            let mut deps = Deps::new();
+            for _ in 0..dec.uint()? {
+                deps.insert_mut(match dec.byte()? {
+                    0 => Dep::Sat,
+                    1 => Dep::Symbol(dec.str()?.into()),
+                    2 => Dep::Term(dec.str()?.into()),
+                    _ => return Err(ImageError::Malformed("unknown dependency")),
+                });
+            }
+            if syms.insert(name, Symbol { kind, el, deps }).is_some() {
+                return Err(ImageError::Malformed("duplicate symbol"));
+            }
+        }
+        if !dec.buf.is_empty() {
+            return Err(ImageError::Malformed("trailing data"));
+        }
+
+        for ((id, name), used) in objects.iter().zip(arities) {
+            let rebound = match self.sym_table.get(name) {
+                Some(x) if x.kind != SymbolKind::Object => {
+                    return Err(ImageError::Conflict { name: name.clone() })
+                }
+                x => x.is_some(),
+            };
+            let arity = self
+                .sym_table
+                .get(name)
+                .or_else(|| syms.get(name))
+                .filter(|x| x.kind == SymbolKind::Object)
+                .and_then(|x| object_arity(&x.el, id));
+            match (arity, used) {
+                (None, _) => return Err(ImageError::Malformed("object without its symbol")),
+                (Some(n), Some(m)) if n != m && rebound => {
+                    return Err(ImageError::Conflict { name: name.clone() })
+                }
+                (Some(n), Some(m)) if n != m => {
+                    return Err(ImageError::Malformed("object used with another arity"))
+                }
+                _ => (),
+            }
+        }
+        if let Some((name, _)) = syms
+            .iter()
+            .find(|(name, x)| x.kind == SymbolKind::Axiom && !self.sym_table.contains_key(*name))
+        {
+            return Err(ImageError::UnknownAxiom { name: name.clone() });
+        }
+
+        let known = |s: &str| syms.contains_key(s) || self.sym_table.contains_key(s);
+        let dangling = syms.values().flat_map(|x| x.deps.iter()).any(|x| match x {
+            Dep::Symbol(s) | Dep::Term(s) => !known(s),
+            Dep::Sat => false,
+        });
+        if dangling {
+            return Err(ImageError::Malformed("unknown dependency"));
+        }
+        let mut added = Vec::new();
+        for (name, sym) in syms {
+            match self.sym_table.get(&name) {
+                Some(old) => {
+                    if old.kind != sym.kind
+                        || !TypedElement::check_equal(
+                            &old.el,
+                            &sym.el,
+                            &mut self.ty_reg,
+                            &mut self.eq_memo,
+                            &mut fuel,
+                        )?
+                    {
+                        return Err(ImageError::Conflict { name });
+                    }
+                }
+                None => added.push((name, sym)),
+            }
+        }
+        for (name, sym) in added {
+            self.sym_table.insert_mut(name, sym);
+        }
+        for (id, name) in new_objects {
+            self.obj_names.insert_mut(id, name);
+        }
+        Ok(())
+    }
+
+    /// Whether `ty` is the type the verifier gives to a node of `data`. The
+    /// type of a bound variable is not part of the type of its binder, so a
+    /// variable is only checked through its arguments.
+    fn type_checks(&mut self, data: &CacheEnum<G, Rc<TypedElement<G>>>, ty: &Type) -> bool {
+        match data {
+            CacheEnum::Primitive(Element::Object { .. }) => *ty == self.ty_reg.symbol(),
+            CacheEnum::Primitive(Element::Universal { body }) => {
+                let sym = self.ty_reg.symbol();
+                *ty == self.ty_reg.infer(sym, body.ty.clone())
+            }
+            CacheEnum::Primitive(Element::Variable { .. }) => true,
+            CacheEnum::Bind { func, arg } => func.ty.apply(&arg.ty).is_ok_and(|x| x == *ty),
+            CacheEnum::RefShift(p, _) => p.ty == *ty,
+        }
+    }
+}
+
+/// The arity of object `id` if `el` is its symbol, `∀a. ∀b. id(a, b)`
+fn object_arity<G: IdGenerator>(el: &Rc<TypedElement<G>>, id: &G::Id) -> Option<usize> {
+    let mut el = el.clone();
+    let mut n = 0;
+    loop {
+        let data = el.data.borrow().clone();
+        match data {
+            CacheEnum::Primitive(Element::Universal { body }) => {
+                el = body;
+                n += 1;
+            }
+            CacheEnum::Primitive(Element::Object { id: x, args }) => {
+                let is_var = |i: usize, x: &Rc<TypedElement<G>>| {
+                    matches!(
+                        x.data.borrow().deref(),
+                        CacheEnum::Primitive(Element::Variable { pos, args })
+                            if pos.get() == n - i && args.is_empty()
+                    )
+                };
+                let vars = args.len() == n && args.iter().enumerate().all(|(i, x)| is_var(i, x));
+                return (x == *id && vars).then_some(n);
+            }
+            _ => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        asm, isa::InstructionSet, kernel::Intuitionistic, util::CountGenerator, vm::Limits,
+    };
+
+    const REFL: &str = "
+        syn uni var qed
+          req sys::l2
+            syn arg 1 app
+            syn req sys::imply syn arg 1 app syn arg 1 app app
+            syn arg 1 app
+          req sys::l1
+            syn arg 1 app
+            syn req sys::imply syn arg 1 app syn arg 1 app app
+          mp
+          req sys::l1 syn arg 1 app syn arg 1 app
+          mp
+        qed
+        hyp refl
+        syn req refl hyp refl2
+        obj 1 box
+    ";
+
+    #[test]
+    fn test_image() {
+        let mut vm: Verifier = Verifier::default();
+        asm::run(&mut vm, REFL).unwrap();
+        let mut image = Vec::new();
+        vm.save(&mut image).unwrap();
+
+        let mut vm: Verifier = Verifier::default();
+        asm::run(&mut vm, "obj 0 first").unwrap();
+        vm.load(&image[..]).unwrap();
+        assert!(Rc::ptr_eq(
+            &vm.sym_table["refl"].el,
+            &vm.sym_table["refl2"].el
+        ));
+        assert_eq!(vm.kind("sys::l3"), Some(SymbolKind::Axiom));
+        assert_eq!(vm.kind("box"), Some(SymbolKind::Object));
+        assert_eq!(vm.assumptions("refl2").unwrap().len(), 5);
+        // `box` got a fresh id, distinct from `first`
+        asm::run(
+            &mut vm,
+            "syn req refl2 syn req box syn req first app app req box syn req first app mp hyp t",
+        )
+        .unwrap();
+        assert!(asm::run(
+            &mut vm,
+            "syn req refl2 syn req box syn req first app app req first mp"
+        )
+        .is_err());
+        // loading again changes nothing
+        vm.load(&image[..]).unwrap();
+
+        let mut vm: Verifier = Verifier::default();
+        asm::run(&mut vm, "syn uni var qed arg 1 qed hyp refl").unwrap();
+        match vm.load(&image[..]) {
+            Err(ImageError::Conflict { name }) => assert_eq!(name, "refl"),
+            _ => panic!(),
+        }
+        assert!(!vm.has("refl2") && !vm.has("box"));
+        // an object whose name is taken by a hypothesis
+        let mut vm: Verifier = Verifier::default();
+        asm::run(&mut vm, "syn req sys::l1 hyp box").unwrap();
+        match vm.load(&image[..]) {
+            Err(ImageError::Conflict { name }) => assert_eq!(name, "box"),
+            _ => panic!(),
+        }
+        assert!(!vm.has("refl"));
+        assert!(matches!(
+            vm.load(&image[..9]),
+            Err(ImageError::Malformed(_))
+        ));
+
+        // within the limits of the verifier
+        let mut vm: Verifier = Verifier::default();
+        vm.set_limits(Limits {
+            max_steps: Some(5),
+            ..Limits::default()
+        });
+        assert!(matches!(
+            vm.load(&image[..]),
+            Err(ImageError::LimitExceeded(Limit::Steps(5)))
+        ));
+        assert!(!vm.has("refl"));
+    }
+
+    /// An image of `vm` with the hypothesis `bad` of element `el`
+    fn image_with(mut vm: Verifier, el: TypedElement<CountGenerator>) -> Vec<u8> {
+        let bad = Symbol {
+            kind: SymbolKind::Hypothesis,
+            el: Rc::new(el),
+            deps: Deps::new(),
+        };
+        vm.sym_table.insert_mut("bad".into(), bad);
+        let mut image = Vec::new();
+        vm.save(&mut image).unwrap();
+        image
+    }
+
+    #[test]
+    fn test_malformed() {
+        // `sys::imply(p)` would pass as an implication
+        let mut vm: Verifier = Verifier::default();
+        asm::run(&mut vm, "obj 0 p").unwrap();
+        let p = vm.sym_table["p"].el.clone();
+        let imply = Element::Object {
+            id: vm.imply_id.unwrap(),
+            args: vec![p.clone()],
+        };
+        let sym = vm.ty_reg.symbol();
+        let image = image_with(vm, TypedElement::new_primitive(imply, sym));
+        match Verifier::<CountGenerator>::default().load(&image[..]) {
+            Err(ImageError::Malformed(what)) => assert_eq!(what, "object used with another arity"),
+            _ => panic!(),
+        }
+
+        // a universal typed as a symbol
+        let mut vm: Verifier = Verifier::default();
+        asm::run(&mut vm, "obj 0 p").unwrap();
+        let body = vm.sym_table["p"].el.clone();
+        let sym = vm.ty_reg.symbol();
+        let image = image_with(
+            vm,
+            TypedElement::new_primitive(Element::Universal { body }, sym),
+        );
+        match Verifier::<CountGenerator>::default().load(&image[..]) {
+            Err(ImageError::Malformed(what)) => assert_eq!(what, "ill-typed element"),
+            _ => panic!(),
+        }
+
+        // an object rebound by name must keep its arity
+        let mut vm: Verifier = Verifier::default();
+        asm::run(&mut vm, "obj 1 f obj 0 q syn req f syn req q app hyp h").unwrap();
+        let mut image = Vec::new();
+        vm.save(&mut image).unwrap();
+        let mut vm: Verifier = Verifier::default();
+        asm::run(&mut vm, "obj 2 f").unwrap();
+        match vm.load(&image[..]) {
+            Err(ImageError::Conflict { name }) => assert_eq!(name, "f"),
+            _ => panic!(),
+        }
+        assert!(!vm.has("h"));
+
+        // `sys::l3` cannot be brought into an intuitionistic verifier
+        let vm: Verifier = Verifier::default();
+        let mut image = Vec::new();
+        vm.save(&mut image).unwrap();
+        let mut vm: Verifier = Verifier::with_kernel(Default::default(), &Intuitionistic).unwrap();
+        match vm.load(&image[..]) {
+            Err(ImageError::UnknownAxiom { name }) => assert_eq!(name, "sys::l3"),
+            _ => panic!(),
+        }
+        assert!(!vm.has("sys::l3"));
+    }
+}
@@ -0,0 +1,167 @@
+//! Ending a session and freezing its symbols.
+
+use std::{collections::BTreeSet, error::Error, fmt, io::Write};
+
+use super::{show::binder_name, Assumption, StackElement, SymbolKind, Type, Verifier};
+use crate::{
+    err::Result,
+    util::{CountGenerator, IdGenerator},
+};
+
+/// An entry left on the stack, at `depth` counted from the bottom, from 1
+#[derive(Debug)]
+pub enum Leftover {
+    /// `syn` without the `def`, `hyp` or `app` that consumes it
+    Syn { depth: usize },
+    /// `uni` still declaring types
+    Uni { depth: usize, types: Vec<Type> },
+    /// Argument of a `uni` whose body was never closed by `qed`
+    Argument {
+        depth: usize,
+        name: String,
+        ty: Type,
+    },
+    /// Element that was never exported
+    Element { depth: usize, term: String },
+}
+
+impl fmt::Display for Leftover {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Leftover::Syn { depth } => write!(f, "{}: unterminated syn", depth),
+            Leftover::Uni { depth, types } => {
+                let types: Vec<_> = types.iter().map(ToString::to_string).collect();
+                write!(f, "{}: unterminated uni [{}]", depth, types.join(", "))
+            }
+            Leftover::Argument { depth, name, ty } => {
+                write!(f, "{}: argument {}: {} without qed", depth, name, ty)
+            }
+            Leftover::Element { depth, term } => {
+                write!(f, "{}: unexported element {}", depth, term)
+            }
+        }
+    }
+}
+
+/// The session did not end with an empty stack
+#[derive(Debug)]
+pub struct FinishError {
+    pub leftovers: Vec<Leftover>,
+}
+
+impl fmt::Display for FinishError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} entries left on the stack", self.leftovers.len())?;
+        for x in &self.leftovers {
+            write!(f, "\n  {}", x)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for FinishError {}
+
+impl<G: IdGenerator> Verifier<G> {
+    /// Check that every block has been terminated and every element exported
+    pub fn finish(&mut self) -> std::result::Result<(), FinishError> {
+        let mut leftovers = Vec::new();
+        let mut args = 0;
+        for i in 0..self.stack.len() {
+            let depth = i + 1;
+            leftovers.push(match &self.stack[i] {
+                StackElement::Synthetic => Leftover::Syn { depth },
+                StackElement::Types(types) => Leftover::Uni {
+                    depth,
+                    types: types.clone(),
+                },
+                StackElement::Argument => {
+                    args += 1;
+                    Leftover::Argument {
+                        depth,
+                        name: binder_name(args - 1),
+                        ty: self.arg_stack[args - 1].clone(),
+                    }
+                }
+                StackElement::Element(el, _) => {
+                    let el = el.clone();
+                    Leftover::Element {
+                        depth,
+                        term: self.printer().show(&el, args),
+                    }
+                }
+            });
+        }
+        if leftovers.is_empty() {
+            Ok(())
+        } else {
+            Err(FinishError { leftovers })
+        }
+    }
+
+    /// Finish the session and keep only its symbols
+    pub fn into_library(mut self) -> std::result::Result<Library<G>, FinishError> {
+        self.finish()?;
+        Ok(Library { vm: self })
+    }
+}
+
+/// The symbol table of a finished session, which can no longer change
+pub struct Library<G: IdGenerator = CountGenerator> {
+    vm: Verifier<G>,
+}
+
+impl<G: IdGenerator> Library<G> {
+    pub fn has(&self, name: &str) -> bool {
+        self.vm.sym_table.contains_key(name)
+    }
+
+    pub fn kind(&self, name: &str) -> Option<SymbolKind> {
+        self.vm.kind(name)
+    }
+
+    pub fn symbols(&self) -> BTreeSet<&str> {
+        self.vm.symbols().collect()
+    }
+
+    pub fn assumptions(&self, name: &str) -> Result<BTreeSet<Assumption>> {
+        self.vm.assumptions(name)
+    }
+
+    pub fn show(&mut self, name: &str) -> Result<String> {
+        self.vm.show(name)
+    }
+
+    pub fn save<W: Write>(&self, w: W) -> std::io::Result<()> {
+        self.vm.save(w)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm;
+
+    #[test]
+    fn test_finish() {
+        let mut vm: Verifier = Verifier::default();
+        asm::run(&mut vm, "syn uni var qed arg 1 uni var var hkt").unwrap();
+        let err = vm.finish().unwrap_err();
+        let lines: Vec<_> = err.leftovers.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            lines,
+            [
+                "1: unterminated syn",
+                "2: argument a: @ without qed",
+                "3: unexported element a",
+                "4: unterminated uni [@=>@]",
+            ]
+        );
+        assert!(vm.into_library().is_err());
+
+        let mut vm: Verifier = Verifier::default();
+        asm::run(&mut vm, "syn uni var qed arg 1 qed hyp id").unwrap();
+        let lib = vm.into_library().unwrap();
+        assert!(lib.has("id") && lib.symbols().contains("sys::l1"));
+        assert_eq!(lib.kind("id"), Some(SymbolKind::Hypothesis));
+    }
+}
@@ -0,0 +1,268 @@
+//! Bounds on the work done for untrusted proofs.
+//!
+//! Flushing a term counts one reduction step per node it visits, and so do
+//! the walks that compare and share terms and the truth-table check of
+//! hypotheses, per node of each formula it evaluates. The step budget is
+//! renewed for every instruction and for the whole of that check, while the
+//! time budget runs from [`Verifier::set_limits`] for the rest of the
+//! session. Rendering a term and loading an image each have the budget of an
+//! instruction.
+
+use std::{
+    fmt,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use super::{TypedElement, Verifier};
+use crate::{
+    err::{OperationError, Result},
+    util::IdGenerator,
+};
+
+/// The clock is read once every this many steps
+const CLOCK_PERIOD: usize = 1 << 12;
+
+/// Resource bounds of a [`Verifier`], none by default
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Reduction steps of a single instruction
+    pub max_steps: Option<usize>,
+    /// Nodes of a flushed term, counting a shared subterm at each occurrence
+    pub max_term_size: Option<usize>,
+    /// Entries on the stack
+    pub max_stack: Option<usize>,
+    /// Wall-clock time of the session
+    pub time: Option<Duration>,
+}
+
+/// A bound of [`Limits`] that was reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Steps(usize),
+    TermSize(usize),
+    Stack(usize),
+    Time(Duration),
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Limit::Steps(n) => write!(f, "more than {} reduction steps", n),
+            Limit::TermSize(n) => write!(f, "a term of more than {} nodes", n),
+            Limit::Stack(n) => write!(f, "more than {} stack entries", n),
+            Limit::Time(t) => write!(f, "time budget of {:?} used up", t),
+        }
+    }
+}
+
+impl Limit {
+    pub(super) fn exceeded_by(self, instruction: &'static str) -> OperationError {
+        OperationError::LimitExceeded {
+            instruction,
+            limit: self,
+        }
+    }
+}
+
+/// The work left to the running instruction
+pub(super) struct Fuel {
+    spent: usize,
+    max_steps: Option<usize>,
+    deadline: Option<(Instant, Duration)>,
+    instruction: &'static str,
+}
+
+impl Fuel {
+    #[cfg(test)]
+    pub(super) fn unlimited() -> Self {
+        Self {
+            spent: 0,
+            max_steps: None,
+            deadline: None,
+            instruction: "",
+        }
+    }
+
+    /// Spend one step
+    pub(super) fn burn(&mut self) -> Result<()> {
+        self.burn_n(1)
+    }
+
+    /// Spend `n` steps at once
+    pub(super) fn burn_n(&mut self, n: usize) -> Result<()> {
+        let before = self.spent;
+        self.spent = self.spent.saturating_add(n);
+        if let Some(max) = self.max_steps {
+            if self.spent > max {
+                return Err(self.exceeded(Limit::Steps(max)));
+            }
+        }
+        if before / CLOCK_PERIOD != self.spent / CLOCK_PERIOD {
+            if let Some((deadline, budget)) = self.deadline {
+                if Instant::now() >= deadline {
+                    return Err(self.exceeded(Limit::Time(budget)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The error of the instruction that burns this fuel
+    pub(super) fn exceeded(&self, limit: Limit) -> OperationError {
+        limit.exceeded_by(self.instruction)
+    }
+}
+
+impl<G: IdGenerator> Verifier<G> {
+    /// Apply `limits` from now on; the time budget starts now
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.deadline = limits.time.map(|t| (Instant::now() + t, t));
+        self.limits = limits;
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// The step budget of one instruction
+    pub(super) fn fuel(&self, instruction: &'static str) -> Fuel {
+        Fuel {
+            spent: 0,
+            max_steps: self.limits.max_steps,
+            deadline: self.deadline,
+            instruction,
+        }
+    }
+
+    /// Fail if the time budget is used up
+    pub(super) fn check_time(&self, instruction: &'static str) -> Result<()> {
+        match self.deadline {
+            Some((deadline, budget)) if Instant::now() >= deadline => {
+                Err(Limit::Time(budget).exceeded_by(instruction))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The canonical node of `el`, within the term size limit
+    pub(super) fn normalize(
+        &mut self,
+        el: &Rc<TypedElement<G>>,
+        fuel: &mut Fuel,
+    ) -> Result<Rc<TypedElement<G>>> {
+        let max_size = self.limits.max_term_size;
+        self.terms.normalize(el, &mut self.ty_reg, fuel, max_size)
+    }
+
+    /// Fail if pushing `n` more entries would exceed the stack limit
+    pub(super) fn expect_room(&self, n: usize, instruction: &'static str) -> Result<()> {
+        match self.limits.max_stack {
+            Some(max) if self.stack.len() + n > max => {
+                Err(Limit::Stack(max).exceeded_by(instruction))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        asm::{self, DiagnosticKind},
+        isa::InstructionSet,
+    };
+
+    fn limit_of(err: OperationError) -> (&'static str, Limit) {
+        match err {
+            OperationError::LimitExceeded { instruction, limit } => (instruction, limit),
+            err => panic!("{}", err),
+        }
+    }
+
+    /// `n` nested applications of `λx. f(x, x)` to `p`, of `2^(n+1) - 1` nodes
+    fn shared(n: usize) -> String {
+        let double = "uni var qed req f syn arg 1 app syn arg 1 app qed syn ";
+        format!("{}req p{}", double.repeat(n), " app".repeat(n))
+    }
+
+    fn doubling(n: usize) -> String {
+        format!("syn {} hyp big", shared(n))
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut vm: Verifier = Verifier::default();
+        asm::run(&mut vm, "obj 2 f obj 0 p").unwrap();
+        vm.set_limits(Limits {
+            max_stack: Some(3),
+            ..Limits::default()
+        });
+        asm::run(&mut vm, "syn syn syn").unwrap();
+        let err = vm.syn().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Limit exceeded by syn: more than 3 stack entries"
+        );
+        assert_eq!(vm.stack.len(), 3);
+        vm.drop_top(3);
+
+        // flushing takes exponentially many steps without sharing
+        vm.set_limits(Limits {
+            max_steps: Some(10_000),
+            ..Limits::default()
+        });
+        let asm::Error::Operation { err, .. } = asm::run(&mut vm, &doubling(40)).unwrap_err()
+        else {
+            panic!()
+        };
+        assert_eq!(limit_of(err), ("hyp", Limit::Steps(10_000)));
+        vm.drop_top(2);
+
+        vm.set_limits(Limits {
+            max_term_size: Some(1000),
+            ..Limits::default()
+        });
+        let asm::Error::Operation { err, .. } = asm::run(&mut vm, &doubling(9)).unwrap_err() else {
+            panic!()
+        };
+        assert_eq!(limit_of(err), ("hyp", Limit::TermSize(1000)));
+        vm.drop_top(2);
+        asm::run(&mut vm, &doubling(8)).unwrap();
+
+        // the rest of the program is skipped once the time is up
+        vm.set_limits(Limits {
+            time: Some(Duration::ZERO),
+            ..Limits::default()
+        });
+        let diags = asm::run_recovering(&mut vm, "obj 0 q obj 0 r").unwrap();
+        assert_eq!(diags.len(), 1);
+        let DiagnosticKind::Error(err) = &diags[0].kind else {
+            panic!()
+        };
+        assert_eq!(
+            err.to_string(),
+            "Limit exceeded by obj: time budget of 0ns used up"
+        );
+    }
+
+    #[test]
+    fn test_mp_shared() {
+        // two copies of a term whose tree has `2^41 - 1` nodes, compared in
+        // steps linear in its graph
+        let mut vm: Verifier = Verifier::default();
+        asm::run(&mut vm, "obj 2 f obj 0 p obj 0 q").unwrap();
+        vm.set_limits(Limits {
+            max_steps: Some(10_000),
+            ..Limits::default()
+        });
+        let src = format!(
+            "syn req sys::imply syn {} app syn req q app {} mp hyp q2",
+            shared(40),
+            shared(40)
+        );
+        asm::run(&mut vm, &src).unwrap();
+        assert_eq!(vm.show("q2").unwrap(), "q");
+    }
+}
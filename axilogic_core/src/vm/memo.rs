@@ -0,0 +1,198 @@
+//! Results of [`TypedElement::check_equal`] kept for the whole session.
+//!
+//! Nodes are identified by address. Equal nodes are merged in a union-find,
+//! so that equalities compose, and each class keeps the nodes known to be
+//! unequal to it. Only weak references are held, which also keeps the address
+//! of a dropped node from being reused while it is in the table; dropped nodes
+//! are swept as the table grows.
+
+use std::{
+    collections::HashMap,
+    rc::{Rc, Weak},
+};
+
+use super::TypedElement;
+use crate::util::IdGenerator;
+
+/// The table is swept when it reaches this many nodes, or twice the number of
+/// live ones after the last sweep
+const MIN_SWEEP: usize = 1024;
+
+pub(super) struct EqMemo<G: IdGenerator> {
+    index: HashMap<usize, usize>,
+    nodes: Vec<Weak<TypedElement<G>>>,
+    parent: Vec<usize>,
+    size: Vec<usize>,
+    /// For a root, nodes of other classes unequal to its class
+    unequal: Vec<Vec<usize>>,
+    sweep_at: usize,
+}
+
+impl<G: IdGenerator> EqMemo<G> {
+    pub(super) fn new() -> Self {
+        Self {
+            index: HashMap::new(),
+            nodes: Vec::new(),
+            parent: Vec::new(),
+            size: Vec::new(),
+            unequal: Vec::new(),
+            sweep_at: MIN_SWEEP,
+        }
+    }
+
+    fn get_index(&self, el: &Rc<TypedElement<G>>) -> Option<usize> {
+        self.index.get(&(Rc::as_ptr(el) as usize)).copied()
+    }
+
+    fn add(&mut self, el: &Rc<TypedElement<G>>) -> usize {
+        if let Some(i) = self.get_index(el) {
+            return i;
+        }
+        let i = self.nodes.len();
+        self.index.insert(Rc::as_ptr(el) as usize, i);
+        self.nodes.push(Rc::downgrade(el));
+        self.parent.push(i);
+        self.size.push(1);
+        self.unequal.push(Vec::new());
+        i
+    }
+
+    fn find(&mut self, mut i: usize) -> usize {
+        let mut root = i;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        while self.parent[i] != root {
+            i = std::mem::replace(&mut self.parent[i], root);
+        }
+        root
+    }
+
+    /// Whether `a` and `b` are known to be equal or unequal
+    pub(super) fn get(&mut self, a: &Rc<TypedElement<G>>, b: &Rc<TypedElement<G>>) -> Option<bool> {
+        let (a, b) = (self.get_index(a)?, self.get_index(b)?);
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return Some(true);
+        }
+        let (a, b) = if self.unequal[a].len() <= self.unequal[b].len() {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        for k in 0..self.unequal[a].len() {
+            if self.find(self.unequal[a][k]) == b {
+                return Some(false);
+            }
+        }
+        None
+    }
+
+    pub(super) fn set_equal(&mut self, a: &Rc<TypedElement<G>>, b: &Rc<TypedElement<G>>) {
+        let (a, b) = (self.add(a), self.add(b));
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            let (root, child) = if self.size[a] >= self.size[b] {
+                (a, b)
+            } else {
+                (b, a)
+            };
+            self.parent[child] = root;
+            self.size[root] += self.size[child];
+            let moved = std::mem::take(&mut self.unequal[child]);
+            self.unequal[root].extend(moved);
+        }
+        self.maybe_sweep();
+    }
+
+    pub(super) fn set_unequal(&mut self, a: &Rc<TypedElement<G>>, b: &Rc<TypedElement<G>>) {
+        let (a, b) = (self.add(a), self.add(b));
+        let (ra, rb) = (self.find(a), self.find(b));
+        self.unequal[ra].push(b);
+        self.unequal[rb].push(a);
+        self.maybe_sweep();
+    }
+
+    /// Rebuild the table from the live nodes, keeping what is known about
+    /// their classes
+    fn maybe_sweep(&mut self) {
+        if self.nodes.len() < self.sweep_at {
+            return;
+        }
+        let mut old = std::mem::replace(self, Self::new());
+        // The new index of the first live node of each old class
+        let mut rep = HashMap::new();
+        for i in 0..old.nodes.len() {
+            if let Some(el) = old.nodes[i].upgrade() {
+                let root = old.find(i);
+                let j = self.add(&el);
+                match rep.get(&root) {
+                    Some(&r) => {
+                        let r = self.find(r);
+                        self.parent[j] = r;
+                        self.size[r] += 1;
+                    }
+                    None => {
+                        rep.insert(root, j);
+                    }
+                }
+            }
+        }
+        for (&root, &r) in &rep {
+            let unequal: Vec<_> = std::mem::take(&mut old.unequal[root]);
+            for x in unequal {
+                let x = old.find(x);
+                if let Some(&y) = rep.get(&x) {
+                    self.unequal[r].push(y);
+                }
+            }
+        }
+        self.sweep_at = (self.nodes.len() * 2).max(MIN_SWEEP);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        asm,
+        vm::{limits::Fuel, Verifier},
+    };
+
+    #[test]
+    fn test_memo() {
+        let mut vm: Verifier = Verifier::default();
+        asm::run(&mut vm, "obj 0 p obj 0 q").unwrap();
+        let term = |vm: &mut Verifier, src: &str| {
+            asm::run(vm, src).unwrap();
+            let (el, _) = vm.peek_element(1).unwrap();
+            vm.drop_top(1);
+            el
+        };
+        let imp = "syn req sys::imply syn req p app syn req p app";
+        let (a, b, c) = (term(&mut vm, imp), term(&mut vm, imp), term(&mut vm, imp));
+        let d = term(&mut vm, "syn req sys::imply syn req p app syn req q app");
+        let mut memo = EqMemo::new();
+        assert_eq!(memo.get(&a, &b), None);
+
+        let mut check = |x, y| {
+            let mut fuel = Fuel::unlimited();
+            TypedElement::check_equal(x, y, &mut vm.ty_reg, &mut memo, &mut fuel).unwrap()
+        };
+        assert!(check(&a, &b));
+        assert!(check(&b, &c));
+        assert!(!check(&a, &d));
+        // by transitivity
+        assert_eq!(memo.get(&a, &c), Some(true));
+        assert_eq!(memo.get(&c, &d), Some(false));
+        assert_eq!(memo.get(&d, &b), Some(false));
+
+        // sweeping keeps the classes of live nodes
+        drop(b);
+        memo.sweep_at = 0;
+        memo.maybe_sweep();
+        assert_eq!(memo.nodes.len(), 3);
+        assert_eq!(memo.get(&a, &c), Some(true));
+        assert_eq!(memo.get(&c, &d), Some(false));
+    }
+}
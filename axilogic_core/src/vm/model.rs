@@ -0,0 +1,458 @@
+//! Truth tables for propositional hypotheses.
+//!
+//! A hypothesis is propositional when, under its leading binders, it is built
+//! from `sys::imply`, `sys::not`, nullary objects and variables without
+//! arguments. Its leading binders range over both truth values, and its
+//! nullary objects are atoms shared by all hypotheses. Hypotheses of any
+//! other shape are not checked.
+//!
+//! The check runs on the fuel of a single instruction, so the step and time
+//! budgets of [`Limits`](super::Limits) bound it; once they are used up the
+//! hypotheses left are reported as skipped.
+
+use std::{fmt, rc::Rc};
+
+use super::{limits::Fuel, show::binder_name, Element, TypedElement, Verifier};
+use crate::{err::Result, util::IdGenerator};
+
+/// Checks enumerate at most `2^MAX_ATOMS` valuations
+const MAX_ATOMS: usize = 20;
+
+/// Deeper hypotheses are not checked, which bounds the recursion on formulas
+const MAX_DEPTH: usize = 1000;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Atom {
+    /// The variable of the leading binder at the given depth
+    Bound(usize),
+    /// An index into [`Objects`]
+    Free(usize),
+}
+
+enum Formula {
+    Atom(Atom),
+    Not(Box<Formula>),
+    Imply(Box<Formula>, Box<Formula>),
+}
+
+impl Formula {
+    fn eval(&self, bound: usize, free: &[bool]) -> bool {
+        match self {
+            Formula::Atom(Atom::Bound(i)) => bound >> i & 1 == 1,
+            Formula::Atom(Atom::Free(i)) => free[*i],
+            Formula::Not(p) => !p.eval(bound, free),
+            Formula::Imply(p, q) => !p.eval(bound, free) || q.eval(bound, free),
+        }
+    }
+
+    /// Number of nodes, the steps spent on an evaluation
+    fn size(&self) -> usize {
+        match self {
+            Formula::Atom(_) => 1,
+            Formula::Not(p) => 1 + p.size(),
+            Formula::Imply(p, q) => 1 + p.size() + q.size(),
+        }
+    }
+
+    fn free(&self, out: &mut Vec<usize>) {
+        match self {
+            Formula::Atom(Atom::Free(i)) => {
+                if !out.contains(i) {
+                    out.push(*i);
+                }
+            }
+            Formula::Atom(Atom::Bound(_)) => (),
+            Formula::Not(p) => p.free(out),
+            Formula::Imply(p, q) => {
+                p.free(out);
+                q.free(out);
+            }
+        }
+    }
+}
+
+/// A propositional hypothesis with its leading binders removed
+struct Hyp {
+    name: String,
+    binders: usize,
+    body: Formula,
+    size: usize,
+    free: Vec<usize>,
+}
+
+impl Hyp {
+    /// Whether the hypothesis holds for every value of its binders
+    fn holds(&self, free: &[bool], fuel: &mut Fuel) -> Result<bool> {
+        for bound in 0..1usize << self.binders {
+            fuel.burn_n(self.size)?;
+            if !self.body.eval(bound, free) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Why a hypothesis is not checked
+enum Unchecked {
+    /// It is not built from the connectives
+    Shape,
+    /// It is too deep or the budget ran out while reading it
+    Skipped,
+}
+
+/// The nullary objects met so far, named
+struct Objects<G: IdGenerator> {
+    ids: Vec<G::Id>,
+    names: Vec<String>,
+}
+
+impl<G: IdGenerator> Objects<G> {
+    fn index(&mut self, id: G::Id, name: &str) -> usize {
+        match self.ids.iter().position(|x| *x == id) {
+            Some(i) => i,
+            None => {
+                self.ids.push(id);
+                self.names.push(name.into());
+                self.ids.len() - 1
+            }
+        }
+    }
+}
+
+/// A hypothesis that is not classically valid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invalid {
+    pub name: String,
+    /// Values of its binders, by their names in [`Verifier::show`], and of its
+    /// objects under which it is false
+    pub valuation: Vec<(String, bool)>,
+}
+
+impl fmt::Display for Invalid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "hyp {} is not classically valid, false when", self.name)?;
+        for (i, (atom, value)) in self.valuation.iter().enumerate() {
+            let sep = if i == 0 { "" } else { "," };
+            write!(f, "{} {} = {}", sep, atom, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// The result of [`Verifier::check_hyps`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModelReport {
+    /// Hypotheses false under some valuation, by name
+    pub invalid: Vec<Invalid>,
+    /// A minimal set of hypotheses that are false together under every
+    /// valuation, empty if none was found
+    pub unsatisfiable: Vec<String>,
+    /// Hypotheses left unchecked: nested deeper than `MAX_DEPTH`, with too
+    /// many atoms to enumerate, or met after the budget ran out
+    pub skipped: Vec<String>,
+}
+
+impl<G: IdGenerator> Verifier<G> {
+    /// Evaluate the propositional hypotheses under all valuations.
+    /// Hypotheses are visited by name, and a hypothesis that would make the
+    /// joint check too large is left out of it and reported as skipped.
+    pub fn check_hyps(&mut self) -> ModelReport {
+        let mut fuel = self.fuel("check_hyps");
+        let mut report = ModelReport::default();
+        let mut names: Vec<_> = self
+            .sym_table
+            .iter()
+            .filter(|(_, sym)| sym.kind == super::SymbolKind::Hypothesis)
+            .map(|(name, sym)| (name.clone(), sym.el.clone()))
+            .collect();
+        names.sort_by(|x, y| x.0.cmp(&y.0));
+        let mut objects = Objects::<G> {
+            ids: Vec::new(),
+            names: Vec::new(),
+        };
+        let mut hyps = Vec::new();
+        for (name, el) in names {
+            match self.propositional(name.clone(), el, &mut objects, &mut fuel) {
+                Ok(hyp) => hyps.push(hyp),
+                Err(Unchecked::Shape) => (),
+                Err(Unchecked::Skipped) => report.skipped.push(name),
+            }
+        }
+
+        let mut consistent: Vec<&Hyp> = Vec::new();
+        // set once the budget runs out, after which nothing is checked
+        let mut exhausted = false;
+        for hyp in &hyps {
+            if exhausted || hyp.binders + hyp.free.len() > MAX_ATOMS {
+                report.skipped.push(hyp.name.clone());
+                continue;
+            }
+            match counter_valuation(hyp, &objects.names, &mut fuel) {
+                Ok(Some(valuation)) => report.invalid.push(Invalid {
+                    name: hyp.name.clone(),
+                    valuation,
+                }),
+                Ok(None) => (),
+                Err(_) => {
+                    exhausted = true;
+                    report.skipped.push(hyp.name.clone());
+                    continue;
+                }
+            }
+            if !report.unsatisfiable.is_empty() {
+                continue;
+            }
+            let mut set = consistent.clone();
+            set.push(hyp);
+            let n_objects = objects.ids.len();
+            match satisfiable(&set, n_objects, &mut fuel) {
+                Ok(Some(true)) => consistent = set,
+                Ok(Some(false)) => match minimize(set, n_objects, &mut fuel) {
+                    Ok(set) => {
+                        report.unsatisfiable = set.into_iter().map(|x| x.name.clone()).collect()
+                    }
+                    Err(_) => {
+                        exhausted = true;
+                        report.skipped.push(hyp.name.clone());
+                    }
+                },
+                Ok(None) => report.skipped.push(hyp.name.clone()),
+                Err(_) => {
+                    exhausted = true;
+                    report.skipped.push(hyp.name.clone());
+                }
+            }
+        }
+        report
+    }
+
+    fn propositional(
+        &mut self,
+        name: String,
+        mut el: Rc<TypedElement<G>>,
+        objects: &mut Objects<G>,
+        fuel: &mut Fuel,
+    ) -> std::result::Result<Hyp, Unchecked> {
+        let mut binders = 0;
+        loop {
+            match el.unwrap_one(&mut self.ty_reg, fuel) {
+                Ok(Element::Universal { body }) => {
+                    el = body;
+                    binders += 1;
+                }
+                Ok(_) => break,
+                Err(_) => return Err(Unchecked::Skipped),
+            }
+        }
+        if binders >= usize::BITS as usize {
+            return Err(Unchecked::Skipped);
+        }
+        let body = self.formula(el, binders, objects, 0, fuel)?;
+        let mut free = Vec::new();
+        body.free(&mut free);
+        Ok(Hyp {
+            name,
+            binders,
+            size: body.size(),
+            body,
+            free,
+        })
+    }
+
+    fn formula(
+        &mut self,
+        mut el: Rc<TypedElement<G>>,
+        binders: usize,
+        objects: &mut Objects<G>,
+        depth: usize,
+        fuel: &mut Fuel,
+    ) -> std::result::Result<Formula, Unchecked> {
+        if depth >= MAX_DEPTH || fuel.burn().is_err() {
+            return Err(Unchecked::Skipped);
+        }
+        let el = el
+            .unwrap_one(&mut self.ty_reg, fuel)
+            .map_err(|_| Unchecked::Skipped)?;
+        match el {
+            Element::Object { id, args } => {
+                let mut args = args.into_iter();
+                let mut arg = |vm: &mut Self, fuel: &mut Fuel| match args.next() {
+                    Some(x) => vm.formula(x, binders, objects, depth + 1, fuel),
+                    None => Err(Unchecked::Shape),
+                };
+                if Some(&id) == self.imply_id.as_ref() {
+                    let p = arg(self, fuel)?;
+                    Ok(Formula::Imply(Box::new(p), Box::new(arg(self, fuel)?)))
+                } else if Some(&id) == self.not_id.as_ref() {
+                    Ok(Formula::Not(Box::new(arg(self, fuel)?)))
+                } else if args.len() == 0 {
+                    let name = self.obj_names.get(&id).ok_or(Unchecked::Shape)?.clone();
+                    Ok(Formula::Atom(Atom::Free(objects.index(id, &name))))
+                } else {
+                    Err(Unchecked::Shape)
+                }
+            }
+            Element::Variable { pos, args } if args.is_empty() => {
+                let level = binders.checked_sub(pos.get()).ok_or(Unchecked::Shape)?;
+                Ok(Formula::Atom(Atom::Bound(level)))
+            }
+            _ => Err(Unchecked::Shape),
+        }
+    }
+}
+
+/// The first valuation of the binders and objects of `hyp` under which it is
+/// false, binders first
+fn counter_valuation(
+    hyp: &Hyp,
+    names: &[String],
+    fuel: &mut Fuel,
+) -> Result<Option<Vec<(String, bool)>>> {
+    let n = hyp.binders + hyp.free.len();
+    let mut free = vec![false; names.len()];
+    for bits in (0..1usize << n).rev() {
+        fuel.burn_n(hyp.size)?;
+        let bit = |i: usize| bits >> (n - 1 - i) & 1 == 1;
+        let bound = (0..hyp.binders).fold(0, |acc, i| acc | (bit(i) as usize) << i);
+        for (i, x) in hyp.free.iter().enumerate() {
+            free[*x] = bit(hyp.binders + i);
+        }
+        if hyp.body.eval(bound, &free) {
+            continue;
+        }
+        let binders = (0..hyp.binders).map(|i| (binder_name(i), bit(i)));
+        let objects = hyp.free.iter().map(|x| (names[*x].clone(), free[*x]));
+        return Ok(Some(binders.chain(objects).collect()));
+    }
+    Ok(None)
+}
+
+/// Whether some valuation of the objects makes every hypothesis hold, or
+/// `None` if they have too many objects together
+fn satisfiable(hyps: &[&Hyp], n_objects: usize, fuel: &mut Fuel) -> Result<Option<bool>> {
+    let mut atoms = Vec::new();
+    for hyp in hyps {
+        for x in &hyp.free {
+            if !atoms.contains(x) {
+                atoms.push(*x);
+            }
+        }
+    }
+    if atoms.len() > MAX_ATOMS {
+        return Ok(None);
+    }
+    let mut free = vec![false; n_objects];
+    'valuations: for bits in 0..1usize << atoms.len() {
+        for (i, x) in atoms.iter().enumerate() {
+            free[*x] = bits >> i & 1 == 1;
+        }
+        for hyp in hyps {
+            if !hyp.holds(&free, fuel)? {
+                continue 'valuations;
+            }
+        }
+        return Ok(Some(true));
+    }
+    Ok(Some(false))
+}
+
+/// Drop the hypotheses not needed for unsatisfiability, one at a time
+fn minimize<'a>(mut hyps: Vec<&'a Hyp>, n_objects: usize, fuel: &mut Fuel) -> Result<Vec<&'a Hyp>> {
+    let mut i = 0;
+    while i < hyps.len() {
+        let hyp = hyps.remove(i);
+        if satisfiable(&hyps, n_objects, fuel)? != Some(false) {
+            hyps.insert(i, hyp);
+            i += 1;
+        }
+    }
+    Ok(hyps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asm, kernel::Empty, vm::Limits};
+
+    #[test]
+    fn test_check_hyps() {
+        let mut vm: Verifier = Verifier::default();
+        asm::run(
+            &mut vm,
+            "
+            obj 0 p
+            obj 0 q
+            obj 1 f
+            syn req sys::imply syn req p app syn req p app hyp p_p
+            syn req sys::imply syn req p app syn req q app hyp p_q
+            syn req p hyp p_true
+            syn req sys::not syn req q app hyp not_q
+            syn req f syn req p app hyp f_p
+            syn uni var qed req sys::imply syn arg 1 app syn req p app qed hyp all_p
+            ",
+        )
+        .unwrap();
+        let report = vm.check_hyps();
+        let invalid: Vec<_> = report.invalid.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            invalid,
+            [
+                "hyp all_p is not classically valid, false when a = true, p = false",
+                "hyp not_q is not classically valid, false when q = true",
+                "hyp p_q is not classically valid, false when p = true, q = false",
+                "hyp p_true is not classically valid, false when p = false",
+            ]
+        );
+        // `all_p` forces `p`, the first set found to be unsatisfiable
+        assert_eq!(report.unsatisfiable, ["all_p", "not_q", "p_q"]);
+        assert!(report.skipped.is_empty());
+
+        // `∀a. a -> p` alone is satisfied by `p = true`
+        let mut vm: Verifier = Verifier::default();
+        asm::run(
+            &mut vm,
+            "
+            obj 0 p
+            syn uni var qed req sys::imply syn arg 1 app syn req p app qed hyp all_p
+            ",
+        )
+        .unwrap();
+        let report = vm.check_hyps();
+        assert_eq!(report.invalid.len(), 1);
+        assert!(report.unsatisfiable.is_empty());
+    }
+
+    #[test]
+    fn test_skipped() {
+        let mut vm: Verifier = Verifier::default();
+        // `!!...!p`, nested deeper than `MAX_DEPTH`
+        let deep = format!(
+            "obj 0 p {}syn req p{} hyp deep syn req p hyp p_true",
+            "syn req sys::not ".repeat(MAX_DEPTH),
+            " app".repeat(MAX_DEPTH)
+        );
+        asm::run(&mut vm, &deep).unwrap();
+        let report = vm.check_hyps();
+        assert_eq!(report.skipped, ["deep"]);
+        assert_eq!(report.invalid.len(), 1);
+
+        // nothing is checked once the budget is used up
+        vm.set_limits(Limits {
+            max_steps: Some(0),
+            ..Limits::default()
+        });
+        let report = vm.check_hyps();
+        assert_eq!(report.skipped, ["deep", "p_true"]);
+        assert!(report.invalid.is_empty());
+
+        // negation is the kernel's, not any object named `sys::not`
+        let mut vm: Verifier = Verifier::with_kernel(Default::default(), &Empty).unwrap();
+        asm::run(
+            &mut vm,
+            "obj 1 sys::not obj 0 p syn req sys::not syn req p app hyp not_p",
+        )
+        .unwrap();
+        assert_eq!(vm.check_hyps(), ModelReport::default());
+    }
+}
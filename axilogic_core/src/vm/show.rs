@@ -0,0 +1,192 @@
+//! Rendering elements as text.
+//!
+//! Elements are forced completely, objects are written by their symbol names
+//! and binders are named `a`, `b`, ..., `z`, `a1`, ... by depth, so the same
+//! variable has the same name everywhere in one rendering. Variables that are
+//! free even outside the stack arguments are written `$n`. A rendering is cut
+//! off with `...` once it runs out of the step or time budget of the
+//! verifier.
+
+use std::{fmt::Write, rc::Rc};
+
+use super::{limits::Fuel, ty, Element, ObjectNames, StackElement, TypedElement, Verifier};
+use crate::{
+    err::{OperationError, Result},
+    util::IdGenerator,
+};
+
+pub(super) fn binder_name(i: usize) -> String {
+    let c = (b'a' + (i % 26) as u8) as char;
+    match i / 26 {
+        0 => c.to_string(),
+        n => format!("{}{}", c, n),
+    }
+}
+
+pub(super) struct Printer<'a, G: IdGenerator> {
+    pub(super) ty_reg: &'a mut ty::Registry,
+    pub(super) obj_names: &'a ObjectNames<G>,
+    pub(super) fuel: Fuel,
+}
+
+impl<'a, G: IdGenerator> Printer<'a, G> {
+    /// Render `el` under `depth` binders
+    pub(super) fn show(&mut self, el: &Rc<TypedElement<G>>, depth: usize) -> String {
+        let mut out = String::new();
+        self.write(&mut out, el, depth);
+        out
+    }
+
+    fn write(&mut self, out: &mut String, el: &Rc<TypedElement<G>>, depth: usize) {
+        let mut todo = vec![Piece::Node(el.clone(), depth)];
+        while let Some(piece) = todo.pop() {
+            let (mut el, depth) = match piece {
+                Piece::Str(s) => {
+                    out.push_str(s);
+                    continue;
+                }
+                Piece::Node(el, depth) => (el, depth),
+            };
+            let head = self
+                .fuel
+                .burn()
+                .and_then(|()| el.unwrap_one(self.ty_reg, &mut self.fuel));
+            let Ok(head) = head else {
+                out.push_str("...");
+                return;
+            };
+            let args = match head {
+                Element::Object { id, args } => {
+                    match self.obj_names.get(&id) {
+                        Some(name) => out.push_str(name),
+                        None => out.push_str("?obj"),
+                    }
+                    args
+                }
+                Element::Universal { body } => {
+                    write!(out, "∀{}. ", binder_name(depth)).unwrap();
+                    todo.push(Piece::Node(body, depth + 1));
+                    continue;
+                }
+                Element::Variable { pos, args } => {
+                    match depth.checked_sub(pos.get()) {
+                        Some(i) => out.push_str(&binder_name(i)),
+                        None => write!(out, "${}", pos.get() - depth).unwrap(),
+                    }
+                    args
+                }
+            };
+            if args.is_empty() {
+                continue;
+            }
+            todo.push(Piece::Str(")"));
+            for (i, x) in args.into_iter().enumerate().rev() {
+                todo.push(Piece::Node(x, depth));
+                todo.push(Piece::Str(if i > 0 { ", " } else { "(" }));
+            }
+        }
+    }
+}
+
+/// Pending output of [`Printer::write`]
+enum Piece<G: IdGenerator> {
+    Node(Rc<TypedElement<G>>, usize),
+    Str(&'static str),
+}
+
+impl<G: IdGenerator> Verifier<G> {
+    /// A printer with the budget of one instruction
+    pub(super) fn printer(&mut self) -> Printer<'_, G> {
+        Printer {
+            fuel: self.fuel("show"),
+            ty_reg: &mut self.ty_reg,
+            obj_names: &self.obj_names,
+        }
+    }
+
+    /// Render the element of a symbol, forcing it on the way
+    pub fn show(&mut self, name: &str) -> Result<String> {
+        let el = match self.sym_table.get(name) {
+            Some(sym) => sym.el.clone(),
+            None => return Err(OperationError::SymbolNotFound { name: name.into() }),
+        };
+        Ok(self.printer().show(&el, 0))
+    }
+
+    /// Render the stack from bottom to top, elements with their types.
+    ///
+    /// The arguments currently open are named in order, as if each were bound
+    /// by an enclosing binder.
+    pub fn show_stack(&mut self) -> Vec<String> {
+        let mut depth = 0;
+        let mut ans = Vec::new();
+        for i in 0..self.stack.len() {
+            let line = match &self.stack[i] {
+                StackElement::Argument => {
+                    depth += 1;
+                    format!(
+                        "arg {}: {}",
+                        binder_name(depth - 1),
+                        self.arg_stack[depth - 1]
+                    )
+                }
+                StackElement::Synthetic => "syn".into(),
+                StackElement::Types(vec) => {
+                    let vec: Vec<_> = vec.iter().map(ToString::to_string).collect();
+                    format!("uni [{}]", vec.join(", "))
+                }
+                StackElement::Element(el, _) => {
+                    let el = el.clone();
+                    format!("{} : {}", self.printer().show(&el, depth), el.ty)
+                }
+            };
+            ans.push(line);
+        }
+        ans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm;
+
+    #[test]
+    fn test_show() {
+        let mut vm: Verifier = Verifier::default();
+        assert_eq!(
+            vm.show("sys::l1").unwrap(),
+            "∀a. ∀b. sys::imply(a, sys::imply(b, a))"
+        );
+        asm::run(
+            &mut vm,
+            "
+            syn uni var var hkt qed
+              req sys::l1 syn uni var qed arg 2 syn req sys::not syn arg 1 app app qed app
+            qed
+            ",
+        )
+        .unwrap();
+        // the argument survives the substitution under a binder
+        assert_eq!(
+            vm.show_stack(),
+            [
+                "syn",
+                "∀a. ∀b. sys::imply(∀c. a(sys::not(c)), sys::imply(b, ∀c. a(sys::not(c)))) : @=>@=>@",
+            ]
+        );
+        asm::run(&mut vm, "syn uni var qed req sys::l1 syn arg 1 app").unwrap();
+        assert_eq!(
+            vm.show_stack()[2..],
+            ["syn", "arg a: @", "∀b. sys::imply(a, sys::imply(b, a)) : @=>@"]
+        );
+        assert!(vm.show("nope").is_err());
+
+        // cut off by the step budget
+        vm.set_limits(crate::vm::Limits {
+            max_steps: Some(3),
+            ..Default::default()
+        });
+        assert_eq!(vm.show("sys::l1").unwrap(), "∀a. ∀b. sys::imply(...");
+    }
+}
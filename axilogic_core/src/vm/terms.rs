@@ -0,0 +1,227 @@
+//! Hash-consing of fully flushed terms.
+//!
+//! A node is canonical when it is a primitive whose arguments are canonical.
+//! Canonical nodes are unique up to structure and type, so two of them are
+//! equal exactly when they are the same pointer. The table keys a node by the
+//! addresses of its arguments and only holds weak references: a live node
+//! keeps its arguments alive, so a key that matches a live entry names the
+//! same arguments, and entries of dropped nodes are swept as the table grows.
+
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    num::NonZeroUsize,
+    rc::{Rc, Weak},
+};
+
+use super::{
+    limits::{Fuel, Limit},
+    ty, Element, TypedElement,
+};
+use crate::{err::Result, util::IdGenerator};
+
+/// The table is swept when it reaches this many entries, or twice the number
+/// of live ones after the last sweep
+const MIN_SWEEP: usize = 1024;
+
+enum Shape<G: IdGenerator> {
+    Object(G::Id),
+    Universal,
+    Variable(NonZeroUsize),
+}
+
+impl<G: IdGenerator> PartialEq for Shape<G> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Shape::Object(x), Shape::Object(y)) => x == y,
+            (Shape::Universal, Shape::Universal) => true,
+            (Shape::Variable(x), Shape::Variable(y)) => x == y,
+            _ => false,
+        }
+    }
+}
+
+impl<G: IdGenerator> Eq for Shape<G> {}
+
+impl<G: IdGenerator> Hash for Shape<G> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Shape::Object(id) => (0u8, id).hash(state),
+            Shape::Universal => 1u8.hash(state),
+            Shape::Variable(pos) => (2u8, pos).hash(state),
+        }
+    }
+}
+
+struct Key<G: IdGenerator> {
+    shape: Shape<G>,
+    /// Addresses of the canonical arguments, or of the body
+    args: Vec<usize>,
+    ty: ty::Type,
+}
+
+impl<G: IdGenerator> PartialEq for Key<G> {
+    fn eq(&self, other: &Self) -> bool {
+        self.shape == other.shape && self.args == other.args && self.ty == other.ty
+    }
+}
+
+impl<G: IdGenerator> Eq for Key<G> {}
+
+impl<G: IdGenerator> Hash for Key<G> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.shape.hash(state);
+        self.args.hash(state);
+        self.ty.hash(state);
+    }
+}
+
+pub(super) struct Terms<G: IdGenerator> {
+    map: HashMap<Key<G>, Weak<TypedElement<G>>>,
+    sweep_at: usize,
+}
+
+impl<G: IdGenerator> Terms<G> {
+    pub(super) fn new() -> Self {
+        Self {
+            map: HashMap::new(),
+            sweep_at: MIN_SWEEP,
+        }
+    }
+
+    /// The canonical node of a primitive with canonical arguments
+    fn intern(&mut self, el: Element<G, Rc<TypedElement<G>>>, ty: ty::Type) -> Rc<TypedElement<G>> {
+        let addr = |x: &Rc<TypedElement<G>>| {
+            debug_assert!(x.canonical);
+            Rc::as_ptr(x) as usize
+        };
+        let key = match &el {
+            Element::Object { id, args } => Key {
+                shape: Shape::Object(id.clone()),
+                args: args.iter().map(addr).collect(),
+                ty: ty.clone(),
+            },
+            Element::Universal { body } => Key {
+                shape: Shape::Universal,
+                args: vec![addr(body)],
+                ty: ty.clone(),
+            },
+            Element::Variable { pos, args } => Key {
+                shape: Shape::Variable(*pos),
+                args: args.iter().map(addr).collect(),
+                ty: ty.clone(),
+            },
+        };
+        if let Some(node) = self.map.get(&key).and_then(Weak::upgrade) {
+            return node;
+        }
+        let size = match &el {
+            Element::Object { args, .. } | Element::Variable { args, .. } => args
+                .iter()
+                .fold(1, |acc: usize, x| acc.saturating_add(x.size)),
+            Element::Universal { body } => body.size.saturating_add(1),
+        };
+        let mut node = TypedElement::new_primitive(el, ty);
+        node.canonical = true;
+        node.size = size;
+        let node = Rc::new(node);
+        self.map.insert(key, Rc::downgrade(&node));
+        if self.map.len() >= self.sweep_at {
+            self.map.retain(|_, x| x.strong_count() > 0);
+            self.sweep_at = (self.map.len() * 2).max(MIN_SWEEP);
+        }
+        node
+    }
+
+    /// The canonical node structurally equal to `el`, failing as soon as a
+    /// part of it has more than `max_size` nodes
+    pub(super) fn normalize(
+        &mut self,
+        el: &Rc<TypedElement<G>>,
+        ty_reg: &mut ty::Registry,
+        fuel: &mut Fuel,
+        max_size: Option<usize>,
+    ) -> Result<Rc<TypedElement<G>>> {
+        let mut todo = vec![Task::Visit(el.clone())];
+        let mut done = Vec::new();
+        while let Some(task) = todo.pop() {
+            match task {
+                Task::Visit(el) if el.canonical => done.push(el),
+                Task::Visit(mut el) => {
+                    fuel.burn()?;
+                    let head = el.unwrap_one(ty_reg, fuel)?;
+                    let parts = match &head {
+                        Element::Object { args, .. } | Element::Variable { args, .. } => {
+                            args.clone()
+                        }
+                        Element::Universal { body } => vec![body.clone()],
+                    };
+                    todo.push(Task::Build(head, el.ty.clone()));
+                    todo.extend(parts.into_iter().rev().map(Task::Visit));
+                }
+                Task::Build(head, ty) => {
+                    let head = match head {
+                        Element::Object { id, args } => Element::Object {
+                            id,
+                            args: done.split_off(done.len() - args.len()),
+                        },
+                        Element::Universal { .. } => Element::Universal {
+                            body: done.pop().unwrap(),
+                        },
+                        Element::Variable { pos, args } => Element::Variable {
+                            pos,
+                            args: done.split_off(done.len() - args.len()),
+                        },
+                    };
+                    let node = self.intern(head, ty);
+                    match max_size {
+                        Some(max) if node.size > max => {
+                            return Err(fuel.exceeded(Limit::TermSize(max)))
+                        }
+                        _ => done.push(node),
+                    }
+                }
+            }
+        }
+        Ok(done.pop().unwrap())
+    }
+}
+
+/// Pending work of [`Terms::normalize`]
+enum Task<G: IdGenerator> {
+    /// Push the canonical node of an element
+    Visit(Rc<TypedElement<G>>),
+    /// Replace the canonical parts on top by the canonical node of a head
+    /// with those parts
+    Build(Element<G, Rc<TypedElement<G>>>, ty::Type),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asm, vm::Verifier};
+
+    #[test]
+    fn test_sharing() {
+        let mut vm: Verifier = Verifier::default();
+        asm::run(
+            &mut vm,
+            "
+            obj 0 p
+            syn req sys::imply syn req p app syn req p app hyp p_p
+            syn req sys::imply syn req p app syn req p app hyp p_p2
+            req sys::l1 syn req p app syn req p app def l1_p
+            ",
+        )
+        .unwrap();
+        let el = |vm: &Verifier, s: &str| vm.sym_table[s].el.clone();
+        assert!(Rc::ptr_eq(&el(&vm, "p_p"), &el(&vm, "p_p2")));
+        let mut fuel = Fuel::unlimited();
+        let Ok(Element::Object { args, .. }) =
+            el(&vm, "l1_p").unwrap_one(&mut vm.ty_reg, &mut fuel)
+        else {
+            panic!()
+        };
+        assert!(Rc::ptr_eq(&args[1], &el(&vm, "p_p")));
+    }
+}
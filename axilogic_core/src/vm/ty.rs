@@ -40,35 +40,54 @@ type Ptr = <HashDedup<TypeEnum> as Dedup>::Ptr;
 
 impl TypeEnum {
     fn dfs_check(a: &Ptr, b: &Ptr) -> bool {
-        if a == b {
-            return true;
-        }
         use TypeEnum::*;
-        match (a.deref(), b.deref()) {
-            (Symbol, _) => true,
-            (Inference(p1, q1), Inference(p2, q2)) => {
-                Self::dfs_check(p1, p2) && Self::dfs_check(q1, q2)
+        let mut todo = vec![(a.clone(), b.clone())];
+        while let Some((a, b)) = todo.pop() {
+            if a == b {
+                continue;
+            }
+            match (a.deref(), b.deref()) {
+                (Symbol, _) => (),
+                (Inference(p1, q1), Inference(p2, q2)) => {
+                    todo.push((q1.clone(), q2.clone()));
+                    todo.push((p1.clone(), p2.clone()));
+                }
+                (Inference(..), Symbol) => return false,
             }
-            (Inference(..), Symbol) => false,
         }
+        true
     }
 }
 
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct Type {
     data: Ptr,
 }
 
 impl Type {
+    /// `Some((p, q))` for `p=>q`, `None` for the symbol type
+    pub fn split(&self) -> Option<(Type, Type)> {
+        match self.data.deref() {
+            TypeEnum::Symbol => None,
+            TypeEnum::Inference(p, q) => Some((Type { data: p.clone() }, Type { data: q.clone() })),
+        }
+    }
+
     pub fn apply(&self, spec: &Self) -> Result<Type> {
         use TypeEnum::*;
         match self.data.deref() {
-            Symbol => Err(OperationError::new("cannot apply symbol type")),
+            Symbol => Err(OperationError::CannotApply {
+                func: self.clone(),
+                arg: spec.clone(),
+            }),
             Inference(p, q) => {
                 if TypeEnum::dfs_check(p, &spec.data) {
                     Ok(Type { data: q.clone() })
                 } else {
-                    Err(OperationError::new("Type mismatch for application"))
+                    Err(OperationError::TypeMismatch {
+                        expected: Type { data: p.clone() },
+                        actual: spec.clone(),
+                    })
                 }
             }
         }
@@ -92,3 +111,31 @@ impl std::fmt::Debug for Type {
         }
     }
 }
+
+/// `p=>q` is right associative, so only a function domain is parenthesized
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        enum Piece {
+            Type(Type),
+            Str(&'static str),
+        }
+        let mut todo = vec![Piece::Type(self.clone())];
+        while let Some(piece) = todo.pop() {
+            match piece {
+                Piece::Str(s) => write!(f, "{}", s)?,
+                Piece::Type(ty) => match ty.split() {
+                    None => write!(f, "@")?,
+                    Some((p, q)) => {
+                        todo.push(Piece::Type(q));
+                        if p.split().is_some() {
+                            todo.extend([Piece::Str(")=>"), Piece::Type(p), Piece::Str("(")]);
+                        } else {
+                            todo.extend([Piece::Str("=>"), Piece::Type(p)]);
+                        }
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+}